@@ -0,0 +1,93 @@
+use std::io;
+use std::path::Path;
+
+use audio::Audio;
+use events::Subscriber;
+use paths;
+use simulation::{Event, Simulation};
+
+// How long the announcement for a new achievement stays up, in seconds.
+const ANNOUNCE_TIME: f64 = 3.0;
+
+pub struct Achievement {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str
+}
+
+pub const ACHIEVEMENTS: [Achievement; 6] = [
+    Achievement { id: "first_bite", name: "First bite", description: "Eat a circle" },
+    Achievement { id: "in_the_groove", name: "In the groove", description: "Eat a circle on the beat" },
+    Achievement { id: "full_hands", name: "Full hands", description: "Hold two triangles at once" },
+    Achievement { id: "combo", name: "Combo breaker", description: "Reach a x3 combo" },
+    Achievement { id: "heavyweight", name: "Heavyweight", description: "Grow to 150 pixels across" },
+    Achievement { id: "survivor", name: "Survivor", description: "Stay alive for two minutes" }
+];
+
+#[derive(RustcEncodable, RustcDecodable)]
+struct Unlocked {
+    unlocked: Vec<String>
+}
+
+/// The achievements unlocked so far, by id, and the latest one to announce.
+pub struct Achievements {
+    unlocked: Vec<String>,
+    announce: Option<(&'static str, f64)>
+}
+
+impl Achievements {
+    pub fn new() -> Achievements {
+        Achievements { unlocked: Vec::new(), announce: None }
+    }
+    pub fn load(path: &Path) -> Achievements {
+        let mut achievements = Achievements::new();
+        if let Some(file) = paths::load_toml::<Unlocked>(path, "achievements") {
+            achievements.unlocked = file.unlocked;
+        }
+        achievements
+    }
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        paths::save_toml(path, &Unlocked { unlocked: self.unlocked.clone() })
+    }
+    pub fn is_unlocked(&self, id: &str) -> bool {
+        self.unlocked.iter().any(|u| u == id)
+    }
+    pub fn update(&mut self, dt: f64) {
+        if let Some((name, time)) = self.announce {
+            self.announce = if time > dt { Some((name, time - dt)) } else { None };
+        }
+    }
+    /// The name of the achievement that was just unlocked, while it's still being shown.
+    pub fn announcement(&self) -> Option<&'static str> {
+        self.announce.map(|(name, _)| name)
+    }
+    fn unlock(&mut self, id: &str) {
+        if self.is_unlocked(id) {
+            return;
+        }
+        if let Some(a) = ACHIEVEMENTS.iter().find(|a| a.id == id) {
+            self.unlocked.push(id.to_string());
+            self.announce = Some((a.name, ANNOUNCE_TIME));
+        }
+    }
+}
+
+impl Subscriber for Achievements {
+    fn on_event(&mut self, event: &Event, sim: &Simulation, _audio: &Audio) {
+        match *event {
+            Event::AteCircle { on_beat, .. } => {
+                self.unlock("first_bite");
+                if on_beat {
+                    self.unlock("in_the_groove");
+                }
+                if sim.player.radius >= 150.0 {
+                    self.unlock("heavyweight");
+                }
+            }
+            Event::PickedTriangle { .. } if sim.col_left.is_some() && sim.col_right.is_some() => self.unlock("full_hands"),
+            Event::ReachedCombo { level } if level >= 3 => self.unlock("combo"),
+            Event::Beat { .. } if sim.time_elapsed >= 120.0 => self.unlock("survivor"),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use find_folder;
+
+#[derive(Debug)]
+pub enum AssetError {
+    NoAssetsFolder,
+    Missing(PathBuf),
+    Load(PathBuf, String)
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AssetError::NoAssetsFolder => write!(f, "Could not find the assets folder next to the game"),
+            AssetError::Missing(ref path) => write!(f, "Missing asset {}", path.display()),
+            AssetError::Load(ref path, ref why) => write!(f, "Could not load asset {}: {}", path.display(), why)
+        }
+    }
+}
+
+impl Error for AssetError {
+    fn description(&self) -> &str {
+        match *self {
+            AssetError::NoAssetsFolder => "no assets folder",
+            AssetError::Missing(_) => "missing asset",
+            AssetError::Load(_, _) => "could not load asset"
+        }
+    }
+}
+
+pub fn folder() -> Result<PathBuf, AssetError> {
+    find_folder::Search::ParentsThenKids(3, 3).for_folder("assets").map_err(|_| AssetError::NoAssetsFolder)
+}
+
+/// The path of `name` inside the assets folder, if it's there.
+pub fn file(assets: &Path, name: &str) -> Result<PathBuf, AssetError> {
+    let path = assets.join(name);
+    if path.exists() {
+        Ok(path)
+    } else {
+        Err(AssetError::Missing(path))
+    }
+}
@@ -0,0 +1,153 @@
+use std::path::Path;
+
+use sdl2_mixer::{Channel, Chunk, Group};
+
+use assets::{self, AssetError};
+use events::Subscriber;
+use settings::Settings;
+use simulation::{Event, Simulation};
+
+/// A loaded sound effect or music stem. Sounds that failed to load are silent.
+pub struct Sound {
+    chunk: Option<Chunk>
+}
+
+impl Sound {
+    pub fn silent() -> Sound {
+        Sound { chunk: None }
+    }
+    pub fn load(assets: &Path, name: &str) -> Result<Sound, AssetError> {
+        let path = try!(assets::file(assets, name));
+        match Chunk::from_file(&path) {
+            Ok(chunk) => Ok(Sound { chunk: Some(chunk) }),
+            Err(e) => Err(AssetError::Load(path, e))
+        }
+    }
+}
+
+/// Mixer channels to allocate. The first `MUSIC_CHANNELS` are the music bus, the rest are for sound effects.
+pub const CHANNELS: isize = 32;
+const MUSIC_CHANNELS: isize = 16;
+const MAX_VOLUME: f32 = 128.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Bus {
+    Music,
+    Sfx
+}
+
+impl Bus {
+    fn group(&self) -> Group {
+        match *self {
+            Bus::Music => Group(1),
+            Bus::Sfx => Group(2)
+        }
+    }
+}
+
+/// The mixer, or a silent stand-in when there's no sound device.
+pub struct Audio {
+    enabled: bool,
+    music_volume: f32,
+    sfx_volume: f32,
+    muted: bool
+}
+
+impl Audio {
+    /// Expects the mixer to be open with `CHANNELS` channels allocated.
+    pub fn new(settings: &Settings) -> Audio {
+        Bus::Music.group().add_channels_range(0, MUSIC_CHANNELS - 1);
+        Bus::Sfx.group().add_channels_range(MUSIC_CHANNELS, CHANNELS - 1);
+        let mut audio = Audio { enabled: true, music_volume: 1.0, sfx_volume: 1.0, muted: false };
+        audio.set_volumes(settings);
+        audio
+    }
+    pub fn null() -> Audio {
+        Audio { enabled: false, music_volume: 0.0, sfx_volume: 0.0, muted: true }
+    }
+    /// Loads a sound from the assets folder. Without a mixer, or if the file can't be loaded, the sound is silent.
+    pub fn load(&self, assets: &Path, name: &str) -> Sound {
+        if !self.enabled {
+            return Sound::silent();
+        }
+        match Sound::load(assets, name) {
+            Ok(sound) => sound,
+            Err(e) => {
+                println!("{}, it will be silent", e);
+                Sound::silent()
+            }
+        }
+    }
+    /// Applies the volume settings, including to sounds that are already playing.
+    pub fn set_volumes(&mut self, settings: &Settings) {
+        self.music_volume = settings.master_volume * settings.music_volume;
+        self.sfx_volume = settings.master_volume * settings.sfx_volume;
+        self.muted = settings.muted;
+        if !self.enabled {
+            return;
+        }
+        for i in 0..CHANNELS {
+            let bus = if i < MUSIC_CHANNELS { Bus::Music } else { Bus::Sfx };
+            Channel(i).set_volume(self.volume(bus));
+        }
+    }
+    fn volume(&self, bus: Bus) -> isize {
+        if self.muted {
+            return 0;
+        }
+        let volume = match bus {
+            Bus::Music => self.music_volume,
+            Bus::Sfx => self.sfx_volume
+        };
+        (volume * MAX_VOLUME) as isize
+    }
+    /// Plays the sound once on a free channel of the bus, or cuts off the oldest one if they're all busy.
+    pub fn play(&self, bus: Bus, sound: &Sound) {
+        self.play_with_gain(bus, sound, 1.0);
+    }
+    /// Like `play`, but quieter than the rest of the bus by `gain` (from 0 to 1).
+    pub fn play_with_gain(&self, bus: Bus, sound: &Sound, gain: f64) {
+        let chunk = match sound.chunk {
+            Some(ref chunk) if self.enabled => chunk,
+            _ => return
+        };
+        let group = bus.group();
+        let channel = match group.find_available().or_else(|| group.find_oldest()) {
+            Some(channel) => channel,
+            None => return
+        };
+        channel.set_volume((self.volume(bus) as f64 * gain) as isize);
+        if let Err(e) = channel.play(chunk, 0) {
+            println!("Could not play sound: {}", e);
+        }
+    }
+}
+
+/// The gameplay sound effects.
+pub struct SoundEffects {
+    circle: Sound,
+    triangle: Sound,
+    lost_shield: Sound,
+    death: Sound
+}
+
+impl SoundEffects {
+    pub fn new(audio: &Audio, assets: &Path) -> SoundEffects {
+        SoundEffects { circle: audio.load(assets, "sounds/on_circle.wav"),
+                       triangle: audio.load(assets, "sounds/on_triangle.wav"),
+                       lost_shield: audio.load(assets, "sounds/on_lose_triangle.wav"),
+                       death: audio.load(assets, "sounds/on_death.wav") }
+    }
+}
+
+impl Subscriber for SoundEffects {
+    fn on_event(&mut self, event: &Event, _sim: &Simulation, audio: &Audio) {
+        match *event {
+            Event::AteCircle { .. } => audio.play(Bus::Sfx, &self.circle),
+            Event::PickedTriangle { .. } | Event::PickedPowerUp { .. } => audio.play(Bus::Sfx, &self.triangle),
+            Event::LostShield => audio.play(Bus::Sfx, &self.lost_shield),
+            Event::Died => audio.play(Bus::Sfx, &self.death),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+
+use Vec2;
+
+/// A uniform grid of buckets, so collision checks only look at objects near the player.
+/// Buckets keep their allocations between steps; call `clear` and re-insert every step.
+pub struct Grid {
+    cell_size: f64,
+    cells: HashMap<(i64, i64), Vec<usize>>
+}
+
+impl Grid {
+    pub fn new(cell_size: f64) -> Grid {
+        Grid { cell_size: cell_size, cells: HashMap::new() }
+    }
+    pub fn clear(&mut self) {
+        for bucket in self.cells.values_mut() {
+            bucket.clear();
+        }
+    }
+    fn cell(&self, p: Vec2) -> (i64, i64) {
+        ((p.x / self.cell_size).floor() as i64, (p.y / self.cell_size).floor() as i64)
+    }
+    /// Adds `id` to every cell overlapped by the box from `min` to `max`.
+    pub fn insert(&mut self, id: usize, min: Vec2, max: Vec2) {
+        let (x0, y0) = self.cell(min);
+        let (x1, y1) = self.cell(max);
+        for x in x0..x1 + 1 {
+            for y in y0..y1 + 1 {
+                self.cells.entry((x, y)).or_insert(Vec::new()).push(id);
+            }
+        }
+    }
+    /// Fills `out` with the ids of everything that may overlap the box, in ascending order.
+    pub fn query(&self, min: Vec2, max: Vec2, out: &mut Vec<usize>) {
+        out.clear();
+        let (x0, y0) = self.cell(min);
+        let (x1, y1) = self.cell(max);
+        for x in x0..x1 + 1 {
+            for y in y0..y1 + 1 {
+                if let Some(bucket) = self.cells.get(&(x, y)) {
+                    out.extend(bucket.iter().cloned());
+                }
+            }
+        }
+        out.sort();
+        out.dedup();
+    }
+}
+
+/// The box covering something of the given diameter as it moves from `a` to `b`.
+pub fn swept_box(a: Vec2, b: Vec2, diameter: f64) -> (Vec2, Vec2) {
+    let r = diameter / 2.0;
+    (Vec2::new(a.x.min(b.x) - r, a.y.min(b.y) - r), Vec2::new(a.x.max(b.x) + r, a.y.max(b.y) + r))
+}
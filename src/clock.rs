@@ -0,0 +1,37 @@
+pub const BEATS_PER_BAR: u64 = 4;
+
+/// The tempo speeds up as the player grows; at the start it's about 142 BPM.
+pub fn bpm(grown: i64) -> f64 {
+    80.0 * ((grown + 10) as f64).sqrt().sqrt()
+}
+
+/// Counts beats at a tempo that can change from one update to the next.
+pub struct BeatClock {
+    beat: u64,
+    phase: f64
+}
+
+impl BeatClock {
+    pub fn new() -> BeatClock {
+        BeatClock { beat: 0, phase: 0.0 }
+    }
+    /// The beat currently playing, starting from 0.
+    pub fn beat(&self) -> u64 {
+        self.beat
+    }
+    /// How far through the current beat we are, from 0 to 1.
+    pub fn phase(&self) -> f64 {
+        self.phase
+    }
+    /// Moves the clock on by `dt` seconds and returns how many new beats started.
+    pub fn advance(&mut self, dt: f64, bpm: f64) -> u64 {
+        self.phase += dt * bpm / 60.0;
+        let mut started = 0;
+        while self.phase >= 1.0 {
+            self.phase -= 1.0;
+            self.beat += 1;
+            started += 1;
+        }
+        started
+    }
+}
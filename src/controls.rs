@@ -0,0 +1,340 @@
+use std::collections::BTreeMap;
+
+use piston_window::{Button, Key, MouseButton};
+use sdl2::Sdl;
+use sdl2::controller::{Axis, Button as PadButton, GameController, GameControllerSubsystem};
+use sdl2::event::{Event, EventPump};
+
+use settings::Settings;
+
+// Stick deflections smaller than this are ignored, so a worn stick doesn't make the player drift.
+const DEAD_ZONE: f64 = 0.2;
+
+/// Something the player can do, whatever it's bound to. Screens ignore the actions that mean nothing to them,
+/// so the same key can do different things in a run and in the menus.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    SwapLeft,
+    SwapRight,
+    MenuUp,
+    MenuDown,
+    MenuLeft,
+    MenuRight,
+    Pause,
+    Mute,
+    Back,
+    Confirm,
+    Credits,
+    HighScores,
+    Settings,
+    NewSeed,
+    Quit,
+    // Starts a run on this entry of the difficulty list.
+    Difficulty(usize)
+}
+
+/// Every action, in the order the rebinding screen lists them.
+pub const ACTIONS: [Action; 29] = [Action::MoveUp, Action::MoveDown, Action::MoveLeft, Action::MoveRight,
+                                   Action::SwapLeft, Action::SwapRight,
+                                   Action::MenuUp, Action::MenuDown, Action::MenuLeft, Action::MenuRight,
+                                   Action::Pause, Action::Mute,
+                                   Action::Back, Action::Confirm, Action::Credits, Action::HighScores,
+                                   Action::Settings, Action::NewSeed, Action::Quit,
+                                   Action::Difficulty(0), Action::Difficulty(1), Action::Difficulty(2),
+                                   Action::Difficulty(3), Action::Difficulty(4), Action::Difficulty(5),
+                                   Action::Difficulty(6), Action::Difficulty(7), Action::Difficulty(8),
+                                   Action::Difficulty(9)];
+
+impl Action {
+    /// The action's key in the settings file.
+    pub fn name(&self) -> String {
+        match *self {
+            Action::MoveUp => "move_up".to_string(),
+            Action::MoveDown => "move_down".to_string(),
+            Action::MoveLeft => "move_left".to_string(),
+            Action::MoveRight => "move_right".to_string(),
+            Action::SwapLeft => "swap_left".to_string(),
+            Action::SwapRight => "swap_right".to_string(),
+            Action::MenuUp => "menu_up".to_string(),
+            Action::MenuDown => "menu_down".to_string(),
+            Action::MenuLeft => "menu_left".to_string(),
+            Action::MenuRight => "menu_right".to_string(),
+            Action::Pause => "pause".to_string(),
+            Action::Mute => "mute".to_string(),
+            Action::Back => "back".to_string(),
+            Action::Confirm => "confirm".to_string(),
+            Action::Credits => "credits".to_string(),
+            Action::HighScores => "high_scores".to_string(),
+            Action::Settings => "settings".to_string(),
+            Action::NewSeed => "new_seed".to_string(),
+            Action::Quit => "quit".to_string(),
+            Action::Difficulty(i) => format!("difficulty_key_{}", LEVEL_DIGITS[i])
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Action> {
+        ACTIONS.iter().find(|a| a.name() == name).cloned()
+    }
+    // Steering and moving through menus in the same direction share keys on purpose.
+    fn shares_with(&self, other: Action) -> bool {
+        match (*self, other) {
+            (Action::MoveUp, Action::MenuUp) | (Action::MenuUp, Action::MoveUp) |
+            (Action::MoveDown, Action::MenuDown) | (Action::MenuDown, Action::MoveDown) |
+            (Action::MoveLeft, Action::MenuLeft) | (Action::MenuLeft, Action::MoveLeft) |
+            (Action::MoveRight, Action::MenuRight) | (Action::MenuRight, Action::MoveRight) => true,
+            (a, b) => a == b
+        }
+    }
+}
+
+// The number key each entry of the difficulty list is on by default. The original levels are on 1-4 and 0,
+// their fairer takes on 5-7 and custom ones on 8 and 9.
+const LEVEL_DIGITS: [usize; 10] = [1, 2, 3, 4, 0, 5, 6, 7, 8, 9];
+const LEVEL_KEYS: [Key; 10] = [Key::D1, Key::D2, Key::D3, Key::D4, Key::D0, Key::D5, Key::D6, Key::D7, Key::D8, Key::D9];
+
+/// A key or button an action can be bound to.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Binding {
+    Key(Key),
+    Mouse(MouseButton),
+    Pad(PadButton)
+}
+
+// Mouse buttons that can be bound; the rest are too rare to bother with.
+const MOUSE_BUTTONS: [MouseButton; 5] = [MouseButton::Left, MouseButton::Right, MouseButton::Middle, MouseButton::X1, MouseButton::X2];
+
+impl Binding {
+    pub fn from_button(button: Button) -> Option<Binding> {
+        match button {
+            Button::Keyboard(k) => Some(Binding::Key(k)),
+            Button::Mouse(m) => Some(Binding::Mouse(m))
+        }
+    }
+    /// How the binding is written in the settings file and shown on screen, e.g. "Key W" or "Pad a".
+    pub fn name(&self) -> String {
+        match *self {
+            Binding::Key(k) => format!("Key {:?}", k),
+            Binding::Mouse(m) => format!("Mouse {:?}", m),
+            Binding::Pad(b) => format!("Pad {}", b.string())
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Binding> {
+        let mut parts = name.splitn(2, ' ');
+        match (parts.next(), parts.next()) {
+            (Some("Key"), Some(key)) => {
+                // Piston keys are SDL keycodes: printable ones are ASCII, the rest start at 0x40000039.
+                (0..0x80).chain(0x40000039..0x4000011B).map(Key::from)
+                    .find(|k| format!("{:?}", k) == key).map(Binding::Key)
+            }
+            (Some("Mouse"), Some(button)) => {
+                MOUSE_BUTTONS.iter().find(|m| format!("{:?}", m) == button).map(|&m| Binding::Mouse(m))
+            }
+            (Some("Pad"), Some(button)) => PadButton::from_string(button).map(Binding::Pad),
+            _ => None
+        }
+    }
+}
+
+/// The default bindings for a control scheme.
+pub fn bindings(scheme: &str) -> Vec<(Binding, Action)> {
+    let mut bindings = match scheme {
+        "Keyboard" => vec![(Binding::Key(Key::W), Action::MoveUp), (Binding::Key(Key::Up), Action::MoveUp),
+                           (Binding::Key(Key::S), Action::MoveDown), (Binding::Key(Key::Down), Action::MoveDown),
+                           (Binding::Key(Key::A), Action::MoveLeft), (Binding::Key(Key::Left), Action::MoveLeft),
+                           (Binding::Key(Key::D), Action::MoveRight), (Binding::Key(Key::Right), Action::MoveRight),
+                           (Binding::Key(Key::Q), Action::SwapLeft), (Binding::Key(Key::E), Action::SwapRight)],
+        "Gamepad" => vec![(Binding::Pad(PadButton::DPadUp), Action::MoveUp),
+                          (Binding::Pad(PadButton::DPadDown), Action::MoveDown),
+                          (Binding::Pad(PadButton::DPadLeft), Action::MoveLeft),
+                          (Binding::Pad(PadButton::DPadRight), Action::MoveRight),
+                          (Binding::Pad(PadButton::LeftShoulder), Action::SwapLeft),
+                          (Binding::Pad(PadButton::RightShoulder), Action::SwapRight)],
+        _ => vec![(Binding::Mouse(MouseButton::Left), Action::SwapLeft),
+                  (Binding::Mouse(MouseButton::Right), Action::SwapRight)]
+    };
+    // The menu keys below stay clear of every scheme's own, so steering or swapping never does something else.
+    if scheme == "Gamepad" {
+        bindings.extend(vec![(Binding::Pad(PadButton::DPadUp), Action::MenuUp),
+                             (Binding::Pad(PadButton::DPadDown), Action::MenuDown),
+                             (Binding::Pad(PadButton::DPadLeft), Action::MenuLeft),
+                             (Binding::Pad(PadButton::DPadRight), Action::MenuRight),
+                             (Binding::Pad(PadButton::Start), Action::Pause),
+                             (Binding::Pad(PadButton::B), Action::Back),
+                             (Binding::Pad(PadButton::A), Action::Confirm)]);
+    }
+    bindings.extend(vec![(Binding::Key(Key::Up), Action::MenuUp), (Binding::Key(Key::Down), Action::MenuDown),
+                         (Binding::Key(Key::Left), Action::MenuLeft), (Binding::Key(Key::Right), Action::MenuRight),
+                         (Binding::Key(Key::P), Action::Pause), (Binding::Key(Key::M), Action::Mute),
+                         (Binding::Key(Key::Escape), Action::Back), (Binding::Key(Key::Return), Action::Confirm),
+                         (Binding::Key(Key::C), Action::Credits), (Binding::Key(Key::H), Action::HighScores),
+                         (Binding::Key(Key::O), Action::Settings), (Binding::Key(Key::N), Action::NewSeed),
+                         (Binding::Key(Key::X), Action::Quit)]);
+    for (i, &k) in LEVEL_KEYS.iter().enumerate() {
+        bindings.push((Binding::Key(k), Action::Difficulty(i)));
+    }
+    bindings
+}
+
+/// The scheme's default bindings, with the actions listed in `overrides` bound to those instead.
+pub fn with_overrides(scheme: &str, overrides: &BTreeMap<String, Vec<String>>) -> Vec<(Binding, Action)> {
+    let mut bindings = bindings(scheme);
+    for (name, names) in overrides {
+        let action = match Action::from_name(name) {
+            Some(action) => action,
+            None => {
+                println!("Ignoring bindings for unknown action {}", name);
+                continue;
+            }
+        };
+        bindings.retain(|&(_, a)| a != action);
+        for name in names {
+            match Binding::from_name(name) {
+                Some(binding) => bindings.push((binding, action)),
+                None => println!("Ignoring unknown binding {} for {}", name, action.name())
+            }
+        }
+    }
+    bindings
+}
+
+/// The gamepads plugged in, read through SDL since the window doesn't report them.
+pub struct Gamepads {
+    subsystem: GameControllerSubsystem,
+    events: EventPump,
+    open: Vec<GameController>
+}
+
+impl Gamepads {
+    pub fn new(sdl: &Sdl) -> Result<Gamepads, String> {
+        let subsystem = try!(sdl.game_controller());
+        let events = try!(sdl.event_pump());
+        let count = try!(subsystem.num_joysticks());
+        let mut pads = Gamepads { subsystem: subsystem, events: events, open: Vec::new() };
+        for i in 0..count {
+            pads.open(i);
+        }
+        Ok(pads)
+    }
+    fn open(&mut self, i: u32) {
+        if !self.subsystem.is_game_controller(i) {
+            return;
+        }
+        match self.subsystem.open(i) {
+            Ok(pad) => {
+                println!("Using gamepad {}", pad.name());
+                self.open.push(pad);
+            }
+            Err(_) => println!("Could not open gamepad {}", i)
+        }
+    }
+}
+
+/// Turns keys, mouse buttons and gamepads into actions, using the scheme and bindings from the settings.
+pub struct Controls {
+    scheme: String,
+    bindings: Vec<(Binding, Action)>,
+    held: Vec<Action>,
+    stick: (f64, f64),
+    pads: Option<Gamepads>
+}
+
+impl Controls {
+    pub fn new(settings: &Settings, pads: Option<Gamepads>) -> Controls {
+        let bindings = match settings.bindings {
+            Some(ref overrides) => with_overrides(&settings.controls, overrides),
+            None => bindings(&settings.controls)
+        };
+        Controls { scheme: settings.controls.clone(), bindings: bindings, held: Vec::new(), stick: (0.0, 0.0), pads: pads }
+    }
+    /// Picks up changes to the control scheme or bindings.
+    pub fn reload(&mut self, settings: &Settings) {
+        *self = Controls::new(settings, self.pads.take());
+    }
+    pub fn actions(&self, binding: Binding) -> Vec<Action> {
+        self.bindings.iter().filter(|&&(b, _)| b == binding).map(|&(_, a)| a).collect()
+    }
+    /// The other actions already on `binding` that binding `action` to it as well would clash with.
+    pub fn clashes(&self, action: Action, binding: Binding) -> Vec<Action> {
+        self.actions(binding).into_iter().filter(|a| !action.shares_with(*a)).collect()
+    }
+    /// What the action is bound to, for showing on screen.
+    pub fn describe(&self, action: Action) -> String {
+        let names: Vec<String> = self.bindings.iter().filter(|&&(_, a)| a == action).map(|&(b, _)| b.name()).collect();
+        if names.len() == 0 {
+            "unbound".to_string()
+        } else {
+            names.join(" / ")
+        }
+    }
+    pub fn press(&mut self, binding: Binding) {
+        for action in self.actions(binding) {
+            if !self.held.contains(&action) {
+                self.held.push(action);
+            }
+        }
+    }
+    /// Actions fire when their key or button is let go.
+    pub fn release(&mut self, binding: Binding) -> Vec<Action> {
+        let actions = self.actions(binding);
+        self.held.retain(|a| !actions.contains(a));
+        actions
+    }
+    /// Reads the gamepads and returns the buttons let go since the last poll, for the caller to turn into actions.
+    pub fn poll(&mut self) -> Vec<Binding> {
+        let events: Vec<Event> = match self.pads {
+            Some(ref mut pads) => pads.events.poll_iter().collect(),
+            None => return Vec::new()
+        };
+        let mut released = Vec::new();
+        for event in events {
+            match event {
+                Event::ControllerDeviceAdded { which, .. } => {
+                    if let Some(ref mut pads) = self.pads {
+                        pads.open(which as u32);
+                    }
+                }
+                Event::ControllerButtonDown { button, .. } => self.press(Binding::Pad(button)),
+                Event::ControllerButtonUp { button, .. } => released.push(Binding::Pad(button)),
+                Event::ControllerAxisMotion { axis, value, .. } => {
+                    let value = value as f64 / 32767.0;
+                    match axis {
+                        Axis::LeftX => self.stick.0 = value,
+                        Axis::LeftY => self.stick.1 = value,
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        released
+    }
+    /// Which way to steer, at most 1 long, or `None` when the player follows the mouse cursor instead.
+    pub fn direction(&self) -> Option<(f64, f64)> {
+        if self.scheme == "Mouse" {
+            return None;
+        }
+        let (mut x, mut y) = (0.0, 0.0);
+        for action in &self.held {
+            match *action {
+                Action::MoveUp => y -= 1.0,
+                Action::MoveDown => y += 1.0,
+                Action::MoveLeft => x -= 1.0,
+                Action::MoveRight => x += 1.0,
+                _ => {}
+            }
+        }
+        if self.scheme == "Gamepad" && (self.stick.0 * self.stick.0 + self.stick.1 * self.stick.1).sqrt() > DEAD_ZONE {
+            x += self.stick.0;
+            y += self.stick.1;
+        }
+        let length = (x * x + y * y).sqrt();
+        if length > 1.0 {
+            Some((x / length, y / length))
+        } else {
+            Some((x, y))
+        }
+    }
+}
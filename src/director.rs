@@ -0,0 +1,51 @@
+use rand::{Isaac64Rng, Rng};
+
+use profile::DifficultyProfile;
+use entities::Entities;
+
+/// Picks the outside colour of each new circle, favouring ones the player can eat right now or after a swap,
+/// and makes sure the player never goes longer than the profile's `max_edible_gap` without something to eat.
+pub struct SpawnDirector {
+    since_edible: f64
+}
+
+impl SpawnDirector {
+    pub fn new() -> SpawnDirector {
+        SpawnDirector { since_edible: 0.0 }
+    }
+    /// Keeps track of how long it's been since a circle of the player's colour was around.
+    pub fn update(&mut self, dt: f64, colour: usize, entities: &Entities) {
+        if entities.iter().any(|(_, e)| e.is_circle() && e.colour == colour) {
+            self.since_edible = 0.0;
+        } else {
+            self.since_edible += dt;
+        }
+    }
+    /// Whether the next circle has to be edible, even if the playfield is already full.
+    pub fn overdue(&self, profile: &DifficultyProfile) -> bool {
+        profile.max_edible_gap > 0.0 && self.since_edible >= profile.max_edible_gap
+    }
+    pub fn pick(&mut self, rng: &mut Isaac64Rng, profile: &DifficultyProfile, colour: usize, held: [Option<usize>; 2]) -> usize {
+        if self.overdue(profile) {
+            self.since_edible = 0.0;
+            return colour;
+        }
+        // Every colour has a weight of 1, plus the biases for the ones the player has or holds.
+        let mut weights = vec![1.0; profile.colour_count];
+        weights[colour] += profile.edible_bias;
+        for &h in held.iter() {
+            if let Some(h) = h {
+                weights[h] += profile.held_bias;
+            }
+        }
+        let total: f64 = weights.iter().fold(0.0, |a, &w| a + w);
+        let mut roll = rng.gen::<f64>() * total;
+        for (i, &w) in weights.iter().enumerate() {
+            if roll < w {
+                return i;
+            }
+            roll -= w;
+        }
+        profile.colour_count - 1
+    }
+}
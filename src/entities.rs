@@ -0,0 +1,131 @@
+use std::iter::Enumerate;
+use std::slice;
+
+use powerups::Power;
+
+/// What an entity is, with whatever only that kind of entity needs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    /// Eaten when its colour is the player's, who then takes on `inside_colour`. Anything else costs a shield.
+    Circle { inside_colour: usize },
+    /// Picked up into a free slot, to swap colours with or to use as a shield.
+    Triangle,
+    /// Does something special when picked up; the colour is the one a colour bomb clears.
+    PowerUp { power: Power }
+}
+
+/// Anything on the playfield besides the player. Every kind has the same components, so moving, expiring and
+/// despawning work the same way for all of them and only the collision response depends on the kind.
+#[derive(Clone, Copy, Debug)]
+pub struct Entity {
+    pub kind: Kind,
+    pub x: f64, pub y: f64,
+    pub vx: f64, pub vy: f64,
+    pub radius: f64,
+    pub colour: usize,
+    // Seconds left before it goes away by itself, or `None` to stay until it leaves the playfield.
+    pub lifetime: Option<f64>
+}
+
+impl Entity {
+    pub fn is_circle(&self) -> bool {
+        match self.kind {
+            Kind::Circle { .. } => true,
+            _ => false
+        }
+    }
+    /// Moves and ages the entity, returning false once its lifetime has run out.
+    pub fn update(&mut self, dt: f64) -> bool {
+        self.x += self.vx * dt;
+        self.y += self.vy * dt;
+        match self.lifetime {
+            Some(ref mut lifetime) => {
+                *lifetime -= dt;
+                *lifetime >= 0.0
+            }
+            None => true
+        }
+    }
+}
+
+/// A pool of entity slots. Despawned slots are reused by later spawns, so a busy playfield doesn't allocate
+/// once the pool has grown to fit it. Ids stay valid until the entity is despawned.
+pub struct Entities {
+    slots: Vec<Option<Entity>>,
+    free: Vec<usize>
+}
+
+impl Entities {
+    pub fn new() -> Entities {
+        Entities { slots: Vec::new(), free: Vec::new() }
+    }
+    pub fn spawn(&mut self, entity: Entity) -> usize {
+        match self.free.pop() {
+            Some(id) => {
+                self.slots[id] = Some(entity);
+                id
+            }
+            None => {
+                self.slots.push(Some(entity));
+                self.slots.len() - 1
+            }
+        }
+    }
+    pub fn despawn(&mut self, id: usize) {
+        if self.slots[id].take().is_some() {
+            self.free.push(id);
+        }
+    }
+    pub fn get(&self, id: usize) -> Option<&Entity> {
+        self.slots.get(id).and_then(|slot| slot.as_ref())
+    }
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Entity> {
+        self.slots.get_mut(id).and_then(|slot| slot.as_mut())
+    }
+    /// How many entities are alive.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+    pub fn count<F: Fn(&Entity) -> bool>(&self, f: F) -> usize {
+        self.iter().filter(|&(_, e)| f(e)).count()
+    }
+    /// The live entities and their ids, in id order.
+    pub fn iter(&self) -> Iter {
+        Iter { slots: self.slots.iter().enumerate() }
+    }
+    pub fn iter_mut(&mut self) -> IterMut {
+        IterMut { slots: self.slots.iter_mut().enumerate() }
+    }
+}
+
+pub struct Iter<'a> {
+    slots: Enumerate<slice::Iter<'a, Option<Entity>>>
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (usize, &'a Entity);
+    fn next(&mut self) -> Option<(usize, &'a Entity)> {
+        while let Some((id, slot)) = self.slots.next() {
+            if let Some(ref entity) = *slot {
+                return Some((id, entity));
+            }
+        }
+        None
+    }
+}
+
+pub struct IterMut<'a> {
+    slots: Enumerate<slice::IterMut<'a, Option<Entity>>>
+}
+
+impl<'a> Iterator for IterMut<'a> {
+    type Item = (usize, &'a mut Entity);
+    fn next(&mut self) -> Option<(usize, &'a mut Entity)> {
+        while let Some((id, slot)) = self.slots.next() {
+            if let Some(ref mut entity) = *slot {
+                return Some((id, entity));
+            }
+        }
+        None
+    }
+}
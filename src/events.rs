@@ -0,0 +1,18 @@
+use audio::Audio;
+use simulation::{Event, Simulation};
+
+/// Something that reacts to what happens in a run. Sounds, music, the HUD, particles, stats and achievements
+/// each get every event and decide for themselves what to do with it, so the simulation doesn't need to know
+/// about any of them.
+pub trait Subscriber {
+    fn on_event(&mut self, event: &Event, sim: &Simulation, audio: &Audio);
+}
+
+/// Hands each event, in order, to every subscriber.
+pub fn publish(events: &[Event], subscribers: &mut [&mut Subscriber], sim: &Simulation, audio: &Audio) {
+    for event in events {
+        for subscriber in subscribers.iter_mut() {
+            subscriber.on_event(event, sim, audio);
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use piston_window::{Graphics, ellipse, line, polygon, rectangle};
+use piston_window::math::Matrix2d;
+
+// Dark enough to show up on every palette colour.
+const INK: [f32; 4] = [0.0, 0.0, 0.0, 0.75];
+
+/// Draws the mark for a colour, `size` pixels across and centred on `transform`, so colours can be told
+/// apart by shape as well as hue. Every colour gets a different mark, however many a level has.
+pub fn draw<G: Graphics>(colour: usize, size: f64, transform: Matrix2d, g: &mut G) {
+    let h = size / 2.0;
+    let thickness = (size / 10.0).max(1.0);
+    match colour % 8 {
+        0 => ellipse(INK, [-h / 2.0, -h / 2.0, h, h], transform, g),
+        1 => rectangle(INK, [-h, -thickness, size, thickness * 2.0], transform, g),
+        2 => rectangle(INK, [-thickness, -h, thickness * 2.0, size], transform, g),
+        3 => {
+            line(INK, thickness, [-h, -h, h, h], transform, g);
+            line(INK, thickness, [-h, h, h, -h], transform, g);
+        }
+        4 => polygon(INK, &[[-h, -h], [h, -h], [0.0, h]], transform, g),
+        5 => rectangle(INK, [-h * 0.7, -h * 0.7, h * 1.4, h * 1.4], transform, g),
+        6 => {
+            line(INK, thickness, [-h, 0.0, h, 0.0], transform, g);
+            line(INK, thickness, [0.0, -h, 0.0, h], transform, g);
+        }
+        _ => polygon(INK, &[[0.0, -h], [h, 0.0], [0.0, h], [-h, 0.0]], transform, g)
+    }
+    // Past the eighth colour the marks come round again, with a dot underneath for each time round.
+    let rounds = colour / 8;
+    let dot = thickness * 2.0;
+    for i in 0..rounds {
+        let x = (i as f64 - (rounds - 1) as f64 / 2.0) * dot * 2.0;
+        ellipse(INK, [x - dot / 2.0, h + dot / 2.0, dot, dot], transform, g);
+    }
+}
@@ -0,0 +1,52 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use paths;
+
+/// How many scores are kept for each difficulty.
+pub const TABLE_SIZE: usize = 10;
+
+#[derive(Clone, RustcEncodable, RustcDecodable)]
+pub struct Entry {
+    pub score: f64,
+    pub time_alive: f64,
+    pub grown: i64,
+    pub date: String,
+    pub seed: usize
+}
+
+/// The top scores for every difficulty, keyed by difficulty name.
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct HighScores {
+    levels: BTreeMap<String, Vec<Entry>>
+}
+
+impl HighScores {
+    pub fn new() -> HighScores {
+        HighScores { levels: BTreeMap::new() }
+    }
+    pub fn load(path: &Path) -> HighScores {
+        paths::load_toml(path, "high score").unwrap_or(HighScores::new())
+    }
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        paths::save_toml(path, self)
+    }
+    pub fn table(&self, level: &str) -> &[Entry] {
+        match self.levels.get(level) {
+            Some(entries) => &entries[..],
+            None => &[]
+        }
+    }
+    /// Returns the 0-based rank if the entry made it into the table.
+    pub fn submit(&mut self, level: &str, entry: Entry) -> Option<usize> {
+        let entries = self.levels.entry(level.to_string()).or_insert(Vec::new());
+        let rank = entries.iter().position(|e| e.score < entry.score).unwrap_or(entries.len());
+        if rank >= TABLE_SIZE {
+            return None;
+        }
+        entries.insert(rank, entry);
+        entries.truncate(TABLE_SIZE);
+        Some(rank)
+    }
+}
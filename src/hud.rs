@@ -0,0 +1,47 @@
+use audio::Audio;
+use events::Subscriber;
+use simulation::{Event, Simulation};
+
+/// The short message that pops up over the game when something worth pointing out happens.
+pub struct Hud {
+    message: String,
+    time_left: f64
+}
+
+impl Hud {
+    pub fn new() -> Hud {
+        Hud { message: String::new(), time_left: 0.0 }
+    }
+    pub fn reset(&mut self) {
+        self.time_left = 0.0;
+    }
+    pub fn update(&mut self, dt: f64) {
+        self.time_left -= dt;
+    }
+    pub fn message(&self) -> Option<&str> {
+        if self.time_left > 0.0 {
+            Some(&self.message[..])
+        } else {
+            None
+        }
+    }
+    fn show(&mut self, message: String, time: f64) {
+        self.message = message;
+        self.time_left = time;
+    }
+}
+
+impl Subscriber for Hud {
+    fn on_event(&mut self, event: &Event, _sim: &Simulation, _audio: &Audio) {
+        match *event {
+            Event::ReachedCombo { level } => self.show(format!("Combo x{}!", level), 1.5),
+            Event::LostShield => self.show("Shield lost".to_string(), 1.0),
+            Event::PickedPowerUp { power, .. } => self.show(format!("{}!", power.name()), 1.5),
+            // Don't cover up a combo with something less exciting.
+            Event::AteCircle { on_beat: true, .. } if self.message().is_none() => {
+                self.show("On the beat!".to_string(), 0.5)
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use piston_window::{Graphics, ellipse, line, polygon, rectangle};
+use piston_window::math::Matrix2d;
+
+use powerups::Power;
+
+const BACKGROUND: [f32; 4] = [0.1, 0.1, 0.1, 0.9];
+const RIM: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const INK: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+/// Draws a power-up, `size` pixels across and centred on `transform`: a ringed dark disc, so it doesn't
+/// pass for a circle, with a mark for what it does. The colour bomb is filled with the colour it clears.
+pub fn draw<G: Graphics>(power: Power, colour: [f32; 4], size: f64, transform: Matrix2d, g: &mut G) {
+    let h = size / 2.0;
+    let ring = (size / 12.0).max(1.0);
+    ellipse(RIM, [-h, -h, size, size], transform, g);
+    ellipse(BACKGROUND, [-h + ring, -h + ring, size - ring * 2.0, size - ring * 2.0], transform, g);
+    let m = h * 0.55;
+    let thickness = (size / 14.0).max(1.0);
+    match power {
+        // An hourglass.
+        Power::SlowTime => {
+            polygon(INK, &[[-m, -m], [m, -m], [0.0, 0.0]], transform, g);
+            polygon(INK, &[[0.0, 0.0], [m, m], [-m, m]], transform, g);
+        }
+        // A horseshoe magnet.
+        Power::Magnet => {
+            line(INK, thickness, [-m, -m, -m, m * 0.5], transform, g);
+            line(INK, thickness, [m, -m, m, m * 0.5], transform, g);
+            line(INK, thickness, [-m, m * 0.5, 0.0, m], transform, g);
+            line(INK, thickness, [m, m * 0.5, 0.0, m], transform, g);
+        }
+        // A bomb with a fuse.
+        Power::ColourBomb => {
+            ellipse(colour, [-m, -m * 0.8, m * 2.0, m * 2.0], transform, g);
+            line(INK, thickness, [m * 0.5, -m * 0.6, m, -m * 1.2], transform, g);
+        }
+        // A shield.
+        Power::Invulnerable => {
+            polygon(INK, &[[-m, -m], [m, -m], [m, 0.0], [0.0, m], [-m, 0.0]], transform, g);
+        }
+        // A plus.
+        Power::Growth => {
+            rectangle(INK, [-m, -thickness, m * 2.0, thickness * 2.0], transform, g);
+            rectangle(INK, [-thickness, -m, thickness * 2.0, m * 2.0], transform, g);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::path::Path;
+
+use audio::{Audio, Bus, Sound};
+use clock::BEATS_PER_BAR;
+use events::Subscriber;
+use simulation::{Event, Simulation};
+
+// How quickly layers fade towards their target volume, in full swings per second.
+const FADE_RATE: f64 = 0.5;
+
+// A string stem plucked on the beats where `beat % every == offset`, as loud as its gain.
+struct Layer {
+    sound: Sound,
+    every: u64,
+    offset: u64,
+    gain: f64
+}
+
+/// The adaptive soundtrack. Every stem is triggered from the simulation's beat clock so they stay in time
+/// with each other and with the gameplay, and the string layers fade in and out with how well the player is doing.
+pub struct Music {
+    bass: Sound,
+    bass2: Sound,
+    bass3: Sound,
+    combo1: Sound,
+    combo2: Sound,
+    combo3: Sound,
+    combo2_beat: Option<u64>,
+    combo3_beat: Option<u64>,
+    strings: Vec<Layer>
+}
+
+fn clamp01(x: f64) -> f64 {
+    x.max(0.0).min(1.0)
+}
+
+impl Music {
+    pub fn new(audio: &Audio, assets: &Path) -> Music {
+        let layer = |name: &str, every: u64, offset: u64| Layer {
+            sound: audio.load(assets, name), every: every, offset: offset, gain: 0.0
+        };
+        let strings = vec![layer("sounds/music/string1.wav", 2, 0),
+                           layer("sounds/music/string2.wav", 4, 1),
+                           layer("sounds/music/string3.wav", 4, 3),
+                           layer("sounds/music/string4.wav", 8, 2),
+                           layer("sounds/music/string5.wav", 1, 0)];
+        Music { bass: audio.load(assets, "sounds/music/bass.wav"),
+                bass2: audio.load(assets, "sounds/music/bass2.wav"),
+                bass3: audio.load(assets, "sounds/music/bass3.wav"),
+                combo1: audio.load(assets, "sounds/music/combo1.wav"),
+                combo2: audio.load(assets, "sounds/music/combo2.wav"),
+                combo3: audio.load(assets, "sounds/music/combo3.wav"),
+                combo2_beat: None, combo3_beat: None,
+                strings: strings }
+    }
+    /// Starts the soundtrack over for a new run.
+    pub fn reset(&mut self) {
+        self.combo2_beat = None;
+        self.combo3_beat = None;
+        for layer in &mut self.strings {
+            layer.gain = 0.0;
+        }
+    }
+    // How loud each string layer should be: bigger, hungrier and better shielded players get a fuller score.
+    fn string_targets(sim: &Simulation) -> [f64; 5] {
+        let shields = (sim.col_left.is_some() as u8 + sim.col_right.is_some() as u8) as f64 / 2.0;
+        [clamp01((sim.player.radius - 40.0) / 40.0),
+         clamp01(sim.grown as f64 / 30.0),
+         shields,
+         clamp01((sim.player.radius - 100.0) / 60.0),
+         clamp01((sim.grown - 60) as f64 / 60.0)]
+    }
+    pub fn update(&mut self, dt: f64, sim: &Simulation) {
+        let targets = Music::string_targets(sim);
+        for (layer, &target) in self.strings.iter_mut().zip(targets.iter()) {
+            let step = FADE_RATE * dt;
+            layer.gain += (target - layer.gain).max(-step).min(step);
+        }
+    }
+    // Triggers the stems due on this beat.
+    fn on_beat(&mut self, beat: u64, sim: &Simulation, audio: &Audio) {
+        if beat % (6 * BEATS_PER_BAR) == 0 {
+            audio.play(Bus::Music, &self.bass2);
+        }
+        if sim.time_elapsed > 10.0 && beat % (3 * BEATS_PER_BAR) == 0 {
+            audio.play(Bus::Music, &self.bass);
+        }
+        if sim.time_elapsed > 20.0 && beat % (2 * BEATS_PER_BAR) == 0 {
+            audio.play(Bus::Music, &self.bass3);
+        }
+        if self.combo2_beat == Some(beat) {
+            audio.play(Bus::Music, &self.combo2);
+            self.combo2_beat = None;
+        }
+        if self.combo3_beat == Some(beat) {
+            audio.play(Bus::Music, &self.combo3);
+            self.combo3_beat = None;
+        }
+        for layer in &self.strings {
+            if layer.gain > 0.01 && beat % layer.every == layer.offset {
+                audio.play_with_gain(Bus::Music, &layer.sound, layer.gain);
+            }
+        }
+    }
+}
+
+impl Subscriber for Music {
+    fn on_event(&mut self, event: &Event, sim: &Simulation, audio: &Audio) {
+        match *event {
+            Event::Beat { beat } => self.on_beat(beat, sim, audio),
+            // Higher combos add stings on the following beats.
+            Event::ReachedCombo { level } => {
+                audio.play(Bus::Music, &self.combo1);
+                if level >= 2 {
+                    self.combo2_beat = Some(sim.clock.beat() + 1);
+                }
+                if level >= 3 {
+                    self.combo3_beat = Some(sim.clock.beat() + 2);
+                }
+            }
+            _ => {}
+        }
+    }
+}
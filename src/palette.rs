@@ -0,0 +1,40 @@
+use std::f32::consts::PI;
+
+use conrod::color::{self, Color};
+
+pub const NAMES: [&'static str; 5] = ["Classic", "Bright", "Colour-blind safe", "Tritanopia", "High contrast"];
+
+// Hand-picked sets for colour-blind players, so they don't rely on telling hues apart.
+// Levels with more colours than these get evenly spaced hues for the rest.
+// Okabe and Ito's set, which works for both kinds of red-green colour blindness.
+const COLOUR_BLIND_SAFE: [u32; 8] = [0xE69F00, 0x56B4E9, 0xF0E442, 0x0072B2, 0xD55E00, 0xCC79A7, 0x009E73, 0xFFFFFF];
+const TRITANOPIA: [u32; 8] = [0xCC3311, 0x009988, 0xEE3377, 0xBBBBBB, 0x332288, 0xFFFFFF, 0x882255, 0x117733];
+const HIGH_CONTRAST: [u32; 8] = [0xFFFFFF, 0xFFFF00, 0x00FFFF, 0xFF00FF, 0x00FF00, 0xFF4000, 0x4080FF, 0x808080];
+
+fn from_hex(hex: u32) -> Color {
+    color::rgb_bytes((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+}
+
+// `count` colours with hues evenly spaced around the colour wheel.
+fn hues(count: usize, s: f32, l: f32) -> Vec<Color> {
+    (0..count).map(|i| {
+        let (r, g, b) = color::hsl_to_rgb(2.0 * PI * i as f32 / count as f32, s, l);
+        color::rgb(r, g, b)
+    }).collect()
+}
+
+/// The player/circle colours for the named palette, `count` of them.
+pub fn colours(name: &str, count: usize) -> Vec<Color> {
+    let fixed = match name {
+        "Colour-blind safe" => COLOUR_BLIND_SAFE,
+        "Tritanopia" => TRITANOPIA,
+        "High contrast" => HIGH_CONTRAST,
+        "Bright" => return hues(count, 0.8, 0.5),
+        _ => return hues(count, 0.5, 0.55)
+    };
+    let mut colours: Vec<Color> = fixed.iter().take(count).map(|&hex| from_hex(hex)).collect();
+    if count > fixed.len() {
+        colours.extend(hues(count, 0.8, 0.5).into_iter().skip(fixed.len()));
+    }
+    colours
+}
@@ -0,0 +1,77 @@
+use std::f64::consts::PI;
+
+use rand::{self, Rng, ThreadRng};
+use piston_window::{Graphics, Transformed, ellipse};
+use piston_window::math::Matrix2d;
+use conrod::color::{Color, Rgba};
+
+use audio::Audio;
+use events::Subscriber;
+use simulation::{Event, Simulation};
+
+// How long a particle lasts, in seconds.
+const LIFE: f64 = 0.6;
+const SIZE: f64 = 6.0;
+
+struct Particle {
+    x: f64, y: f64,
+    vx: f64, vy: f64,
+    life: f64,
+    colour: usize
+}
+
+/// Bursts of dots where circles are eaten and triangles picked up. Purely for show, so they use their own
+/// random numbers and never touch the simulation's.
+pub struct Particles {
+    particles: Vec<Particle>,
+    rng: ThreadRng
+}
+
+impl Particles {
+    pub fn new() -> Particles {
+        Particles { particles: Vec::new(), rng: rand::thread_rng() }
+    }
+    pub fn reset(&mut self) {
+        self.particles.clear();
+    }
+    fn burst(&mut self, count: usize, colour: usize, x: f64, y: f64, speed: f64) {
+        for _ in 0..count {
+            let angle = self.rng.gen::<f64>() * 2.0 * PI;
+            let speed = speed * (0.5 + self.rng.gen::<f64>());
+            self.particles.push(Particle {
+                x: x, y: y, vx: angle.cos() * speed, vy: angle.sin() * speed,
+                life: LIFE * (0.5 + self.rng.gen::<f64>() / 2.0), colour: colour
+            });
+        }
+    }
+    pub fn update(&mut self, dt: f64) {
+        for p in &mut self.particles {
+            p.x += p.vx * dt;
+            p.y += p.vy * dt;
+            p.life -= dt;
+        }
+        self.particles.retain(|p| p.life > 0.0);
+    }
+    /// `transform` puts the origin in the middle of the playfield, like the simulation's.
+    pub fn draw<G: Graphics>(&self, colours: &[Color], transform: Matrix2d, g: &mut G) {
+        for p in &self.particles {
+            let Rgba(r, gg, b, _) = colours[p.colour].to_rgb();
+            let alpha = (p.life / LIFE) as f32;
+            ellipse([r, gg, b, alpha], [-SIZE / 2.0, -SIZE / 2.0, SIZE, SIZE], transform.trans(p.x, p.y), g);
+        }
+    }
+}
+
+impl Subscriber for Particles {
+    fn on_event(&mut self, event: &Event, sim: &Simulation, _audio: &Audio) {
+        let player = &sim.player;
+        match *event {
+            Event::AteCircle { colour, x, y, .. } => self.burst(12, colour, x, y, 150.0),
+            Event::PickedTriangle { colour, x, y } => self.burst(8, colour, x, y, 100.0),
+            Event::PickedPowerUp { colour, x, y, .. } => self.burst(20, colour, x, y, 200.0),
+            Event::LostShield => self.burst(16, player.colour_state, player.x, player.y, 250.0),
+            Event::Died => self.burst(40, player.colour_state, player.x, player.y, 300.0),
+            _ => {}
+        }
+    }
+}
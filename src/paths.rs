@@ -0,0 +1,42 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use rustc_serialize::{Decodable, Encodable};
+use toml;
+
+/// Where per-user files (replays, scores, settings) live: `~/.carket`, or `./.carket` without a home dir.
+pub fn data_dir() -> PathBuf {
+    let dir = env::home_dir().unwrap_or(PathBuf::from(".")).join(".carket");
+    let _ = fs::create_dir_all(&dir);
+    dir
+}
+
+pub fn data_file(name: &str) -> PathBuf {
+    data_dir().join(name)
+}
+
+/// Reads a TOML data file. A missing or unreadable file just means there's nothing saved yet, and a corrupt one
+/// is reported as the named kind of file and then treated the same way.
+pub fn load_toml<T: Decodable>(path: &Path, what: &str) -> Option<T> {
+    load_toml_with(path, what, |table| toml::decode(toml::Value::Table(table)))
+}
+
+/// `load_toml` with a custom decode step, e.g. to fill in missing keys first.
+pub fn load_toml_with<T, F: FnOnce(toml::Table) -> Option<T>>(path: &Path, what: &str, decode: F) -> Option<T> {
+    let mut s = String::new();
+    if File::open(path).and_then(|mut f| f.read_to_string(&mut s)).is_err() {
+        return None;
+    }
+    let value = toml::Parser::new(&s).parse().and_then(decode);
+    if value.is_none() {
+        println!("Ignoring corrupt {} file {}", what, path.display());
+    }
+    value
+}
+
+pub fn save_toml<T: Encodable>(path: &Path, value: &T) -> io::Result<()> {
+    let mut f = try!(File::create(path));
+    f.write_all(toml::encode_str(value).as_bytes())
+}
@@ -0,0 +1,50 @@
+/// What a power-up does when the player picks it up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Power {
+    /// Everything but the player moves at `SLOW_TIME_SPEED` for a while.
+    SlowTime,
+    /// Circles the player can eat get pulled towards them for a while.
+    Magnet,
+    /// Clears every circle of the power-up's colour.
+    ColourBomb,
+    /// Circles of the wrong colour bounce off harmlessly for a while, heading straight away from the player.
+    Invulnerable,
+    /// Makes the player `GROWTH_BURST` bigger at once.
+    Growth
+}
+
+pub const POWERS: [Power; 5] = [Power::SlowTime, Power::Magnet, Power::ColourBomb, Power::Invulnerable, Power::Growth];
+
+pub const SLOW_TIME_SPEED: f64 = 0.5;
+// How far away circles feel the magnet, and how fast it drags them towards the player, in pixels per second.
+pub const MAGNET_RANGE: f64 = 300.0;
+pub const MAGNET_PULL: f64 = 200.0;
+pub const GROWTH_BURST: f64 = 20.0;
+
+impl Power {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Power::SlowTime => "Slow time",
+            Power::Magnet => "Magnet",
+            Power::ColourBomb => "Colour bomb",
+            Power::Invulnerable => "Invulnerable",
+            Power::Growth => "Growth burst"
+        }
+    }
+    /// How long the power lasts, or `None` if it all happens at once.
+    pub fn duration(&self) -> Option<f64> {
+        match *self {
+            Power::SlowTime => Some(5.0),
+            Power::Magnet => Some(6.0),
+            Power::Invulnerable => Some(4.0),
+            Power::ColourBomb | Power::Growth => None
+        }
+    }
+}
+
+/// A power that's still going, with the seconds it has left.
+#[derive(Clone, Copy)]
+pub struct Effect {
+    pub power: Power,
+    pub time_left: f64
+}
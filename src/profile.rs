@@ -0,0 +1,162 @@
+use std::path::Path;
+
+use toml;
+
+use paths;
+
+/// Everything that makes one difficulty level harder than another.
+///
+/// With `t` the time alive and `g` the number of circles eaten:
+/// - circles on screen: `spawn_base + spawn_time * sqrt(t) + spawn_grown * sqrt(g)`
+/// - circle speed: `speed_base + speed_scale * sqrt(t / speed_time + g / speed_grown)`
+/// - the player shrinks back towards the starting size `shrink_rate` times as fast as on the defaults
+/// - circles and triangles come in `colour_count` colours (at least 2, 6 if left out)
+/// - new circles are `1 + edible_bias` times as likely to be the player's colour, and `1 + held_bias` times
+///   as likely to be one held in a triangle slot
+/// - the player never goes `max_edible_gap` seconds without a circle of their colour about (0 turns this off)
+/// - a power-up turns up every `powerup_interval` seconds (0 turns them off)
+///
+/// The biases, the gap and the power-ups are 0 unless a profile sets them, custom or built-in, so a level plays
+/// like the original game unless it asks for more.
+#[derive(Clone, RustcEncodable, RustcDecodable)]
+pub struct DifficultyProfile {
+    pub name: String,
+    pub spawn_base: f64,
+    pub spawn_time: f64,
+    pub spawn_grown: f64,
+    pub speed_base: f64,
+    pub speed_scale: f64,
+    pub speed_time: f64,
+    pub speed_grown: f64,
+    pub first_triangle: f64,
+    pub triangle_interval: f64,
+    pub shrink_rate: f64,
+    pub score_multiplier: f64,
+    pub colour_count: usize,
+    pub edible_bias: f64,
+    pub held_bias: f64,
+    pub max_edible_gap: f64,
+    pub powerup_interval: f64
+}
+
+/// How many colours the original game had.
+pub const CLASSIC_COLOURS: usize = 6;
+
+impl DifficultyProfile {
+    // The original levels were all a single multiplier on the same formulas.
+    fn scaled(name: &str, d: f64) -> DifficultyProfile {
+        DifficultyProfile {
+            name: name.to_string(),
+            spawn_base: 0.0, spawn_time: 3.0, spawn_grown: 0.5 * d,
+            speed_base: 1.0, speed_scale: d, speed_time: 100.0, speed_grown: 10.0,
+            first_triangle: 10.0, triangle_interval: 10.0 * d,
+            shrink_rate: 1.0,
+            score_multiplier: d * d,
+            colour_count: CLASSIC_COLOURS,
+            edible_bias: 0.0, held_bias: 0.0,
+            max_edible_gap: 0.0,
+            powerup_interval: 0.0
+        }
+    }
+    // An original level with circles dealt out more kindly, the easier the level the more so, and power-ups.
+    fn fair(name: &str, d: f64) -> DifficultyProfile {
+        DifficultyProfile {
+            edible_bias: 0.6 / d, held_bias: 0.3 / d,
+            max_edible_gap: 5.0 * d,
+            powerup_interval: 20.0,
+            ..DifficultyProfile::scaled(name, d)
+        }
+    }
+    // What's wrong with a custom profile, if anything, given the ones loaded before it. High scores go by name,
+    // so names can't be shared, and the formulas divide by `speed_time` and `speed_grown`.
+    fn problem(&self, loaded: &[DifficultyProfile]) -> Option<String> {
+        if loaded.iter().any(|p| p.name == self.name) {
+            return Some("there's already a level with that name".to_string());
+        }
+        if self.colour_count < 2 {
+            return Some("it needs at least 2 colours".to_string());
+        }
+        let values = [("spawn_base", self.spawn_base), ("spawn_time", self.spawn_time), ("spawn_grown", self.spawn_grown),
+                      ("speed_base", self.speed_base), ("speed_scale", self.speed_scale),
+                      ("speed_time", self.speed_time), ("speed_grown", self.speed_grown),
+                      ("first_triangle", self.first_triangle), ("triangle_interval", self.triangle_interval),
+                      ("shrink_rate", self.shrink_rate), ("score_multiplier", self.score_multiplier),
+                      ("edible_bias", self.edible_bias), ("held_bias", self.held_bias),
+                      ("max_edible_gap", self.max_edible_gap), ("powerup_interval", self.powerup_interval)];
+        for &(name, value) in values.iter() {
+            if !value.is_finite() {
+                return Some(format!("{} isn't a number", name));
+            }
+            if value < 0.0 {
+                return Some(format!("{} can't be negative", name));
+            }
+        }
+        for &(name, value) in [("speed_time", self.speed_time), ("speed_grown", self.speed_grown),
+                               ("triangle_interval", self.triangle_interval)].iter() {
+            if value == 0.0 {
+                return Some(format!("{} can't be 0", name));
+            }
+        }
+        None
+    }
+    pub fn circle_count(&self, time_elapsed: f64, grown: i64) -> usize {
+        (self.spawn_base + time_elapsed.sqrt() * self.spawn_time + (grown as f64).sqrt() * self.spawn_grown) as usize
+    }
+    pub fn circle_speed(&self, time_elapsed: f64, grown: i64) -> f64 {
+        self.speed_base + (time_elapsed / self.speed_time + (grown as f64) / self.speed_grown).sqrt() * self.speed_scale
+    }
+}
+
+/// The levels that ship with the game: the original Easy, Medium, Hard, UltraHD and the hidden D0, which deal
+/// circles out evenly and have no power-ups like they always did, then fairer takes on the first three with
+/// power-ups.
+pub fn builtin() -> Vec<DifficultyProfile> {
+    vec![DifficultyProfile::scaled("Easy", 0.3),
+         DifficultyProfile::scaled("Medium", 0.6),
+         DifficultyProfile::scaled("Hard", 1.0),
+         DifficultyProfile::scaled("UltraHD", 2.0),
+         DifficultyProfile::scaled("D0", 5.0),
+         DifficultyProfile::fair("Easy+", 0.3),
+         DifficultyProfile::fair("Medium+", 0.6),
+         DifficultyProfile::fair("Hard+", 1.0)]
+}
+
+#[derive(RustcDecodable)]
+struct ProfileFile {
+    profile: Vec<DifficultyProfile>
+}
+
+/// The built-in levels followed by any `[[profile]]` tables in the given TOML file.
+pub fn load(path: &Path) -> Vec<DifficultyProfile> {
+    let mut profiles = builtin();
+    if let Some(file) = paths::load_toml_with(path, "difficulty profile", |t| toml::decode::<ProfileFile>(with_defaults(t))) {
+        for profile in file.profile {
+            match profile.problem(&profiles) {
+                Some(problem) => println!("Ignoring difficulty profile {}, {}", profile.name, problem),
+                None => profiles.push(profile)
+            }
+        }
+    }
+    profiles
+}
+
+// Profiles written before a setting existed get the original game's behaviour for it.
+fn with_defaults(mut file: toml::Table) -> toml::Value {
+    let defaults = [("colour_count", toml::Value::Integer(CLASSIC_COLOURS as i64)),
+                    ("edible_bias", toml::Value::Float(0.0)),
+                    ("held_bias", toml::Value::Float(0.0)),
+                    ("max_edible_gap", toml::Value::Float(0.0)),
+                    ("powerup_interval", toml::Value::Float(0.0))];
+    if let Some(&mut toml::Value::Array(ref mut profiles)) = file.get_mut("profile") {
+        for profile in profiles {
+            if let toml::Value::Table(ref mut profile) = *profile {
+                for &(key, ref value) in defaults.iter() {
+                    if !profile.contains_key(key) {
+                        profile.insert(key.to_string(), value.clone());
+                    }
+                }
+            }
+        }
+    }
+    toml::Value::Table(file)
+}
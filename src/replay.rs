@@ -0,0 +1,163 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use toml;
+
+use simulation::{Simulation, TickInput};
+use profile::DifficultyProfile;
+
+const HEADER: &'static str = "carket-replay 1";
+
+#[derive(Clone, Copy)]
+pub struct Tick {
+    pub dt: f64,
+    pub half_width: f64, pub half_height: f64,
+    pub input: TickInput
+}
+
+/// Everything needed to play a run back: the seed, the difficulty profile and what the player did every tick.
+pub struct Replay {
+    pub seed: usize,
+    pub profile: DifficultyProfile,
+    pub ticks: Vec<Tick>
+}
+
+impl Replay {
+    pub fn new(seed: usize, profile: DifficultyProfile) -> Replay {
+        Replay { seed: seed, profile: profile, ticks: Vec::new() }
+    }
+    pub fn record(&mut self, sim: &Simulation, dt: f64, input: &TickInput) {
+        self.ticks.push(Tick { dt: dt, half_width: sim.half_width, half_height: sim.half_height, input: *input });
+    }
+    /// Runs the whole replay with no window or audio and returns the final state.
+    pub fn simulate(&self) -> Simulation {
+        let mut sim = Simulation::new(self.profile.clone(), self.seed);
+        for tick in &self.ticks {
+            tick.apply(&mut sim);
+            sim.step(tick.dt, &tick.input);
+        }
+        sim
+    }
+    // Floats are written with `Debug`, which always keeps enough digits to read back exactly, so playback is
+    // bit-for-bit.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut f = try!(File::create(path));
+        try!(writeln!(f, "{}", HEADER));
+        try!(writeln!(f, "seed {}", self.seed));
+        // The profile is stored whole, so replays of custom profiles still play back after the profile file changes.
+        try!(writeln!(f, "[profile]"));
+        try!(write!(f, "{}", toml::encode_str(&self.profile)));
+        try!(writeln!(f, "[ticks]"));
+        let (mut hw, mut hh) = (-1.0, -1.0);
+        for tick in &self.ticks {
+            if tick.half_width != hw || tick.half_height != hh {
+                hw = tick.half_width; hh = tick.half_height;
+                try!(writeln!(f, "b {:?} {:?}", hw, hh));
+            }
+            try!(writeln!(f, "t {:?} {:?} {:?} {} {}", tick.dt, tick.input.target_x, tick.input.target_y,
+                          tick.input.swap_left as u8, tick.input.swap_right as u8));
+        }
+        Ok(())
+    }
+    pub fn load(path: &Path) -> io::Result<Replay> {
+        let f = BufReader::new(try!(File::open(path)));
+        let mut lines = f.lines();
+        match lines.next() {
+            Some(Ok(ref l)) if l.trim() == HEADER => {}
+            _ => return Err(bad_data("not a replay file"))
+        }
+        let mut seed = 0;
+        let mut profile = String::new();
+        let mut in_profile = false;
+        let mut ticks = Vec::new();
+        let (mut hw, mut hh) = (300.0, 300.0);
+        for line in lines {
+            let line = try!(line);
+            if in_profile {
+                if line.trim() == "[ticks]" {
+                    in_profile = false;
+                } else {
+                    profile = profile + &line + "\n";
+                }
+                continue;
+            }
+            if line.trim() == "[profile]" {
+                in_profile = true;
+                continue;
+            }
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() == 0 {
+                continue;
+            }
+            match (parts[0], parts.len()) {
+                ("seed", 2) => seed = try!(parse(parts[1])),
+                ("b", 3) => {
+                    hw = try!(parse(parts[1]));
+                    hh = try!(parse(parts[2]));
+                }
+                ("t", 6) => {
+                    let input = TickInput {
+                        target_x: try!(parse(parts[2])), target_y: try!(parse(parts[3])),
+                        swap_left: parts[4] == "1", swap_right: parts[5] == "1"
+                    };
+                    ticks.push(Tick { dt: try!(parse(parts[1])), half_width: hw, half_height: hh, input: input });
+                }
+                _ => return Err(bad_data(&format!("unexpected line: {}", line)))
+            }
+        }
+        if ticks.len() == 0 {
+            return Err(bad_data("no ticks to play"));
+        }
+        match toml::decode_str(&profile) {
+            Some(profile) => Ok(Replay { seed: seed, profile: profile, ticks: ticks }),
+            None => Err(bad_data("missing or invalid difficulty profile"))
+        }
+    }
+}
+
+impl Tick {
+    pub fn apply(&self, sim: &mut Simulation) {
+        sim.half_width = self.half_width;
+        sim.half_height = self.half_height;
+    }
+}
+
+fn parse<T: FromStr>(s: &str) -> io::Result<T> {
+    T::from_str(s).map_err(|_| bad_data(&format!("bad number: {}", s)))
+}
+
+fn bad_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use profile;
+    use simulation::STEP;
+
+    #[test]
+    fn plays_back_exactly_after_saving() {
+        let mut sim = Simulation::new(profile::builtin()[1].clone(), 7);
+        let mut replay = Replay::new(7, profile::builtin()[1].clone());
+        for i in 0..3000 {
+            let t = i as f64;
+            let input = TickInput {
+                target_x: (t * 0.013).sin() * 0.1 * 3000.0, target_y: (t * 0.029).cos() / 3.0 * 700.0,
+                swap_left: i % 200 == 0, swap_right: false
+            };
+            sim.half_width = 400.0 + 1.0 / 3.0;
+            replay.record(&sim, STEP, &input);
+            sim.step(STEP, &input);
+        }
+        let path = env::temp_dir().join("carket-replay-test.replay");
+        replay.save(&path).unwrap();
+        let played = Replay::load(&path).unwrap().simulate();
+        assert_eq!((played.score, played.grown, played.time_elapsed), (sim.score, sim.grown, sim.time_elapsed));
+        assert_eq!((played.player.x, played.player.y), (sim.player.x, sim.player.y));
+    }
+}
@@ -0,0 +1,203 @@
+use piston_window::{Context, G2d, Input, Motion, PistonWindow, RenderArgs, clear, rectangle};
+
+use controls::{Action, Binding};
+use Game;
+use Ui;
+
+// How long each half of a fade between scenes takes, in seconds.
+const FADE_TIME: f64 = 0.25;
+
+/// What a scene wants done to the stack after handling something.
+pub enum Transition {
+    None,
+    /// Put a scene on top, e.g. an overlay over the game.
+    Push(Box<Scene>),
+    /// Go back to the scene underneath.
+    Pop,
+    /// Swap the top scene for another one.
+    Replace(Box<Scene>),
+    /// Fade to black, throw the whole stack away, and fade in on the given scene.
+    FadeTo(Box<Scene>)
+}
+
+/// One screen of the game. Only the top scene gets input and updates, but overlays also show what's under them.
+pub trait Scene {
+    fn update(&mut self, _game: &mut Game, _dt: f64) -> Transition {
+        Transition::None
+    }
+    /// Sets the scene's conrod widgets. Scenes drawn together share one frame of widget ids,
+    /// so overlays and the scene under them have to declare theirs in the same `widget_ids!`.
+    fn widgets(&mut self, game: &mut Game, ui: &mut Ui) -> Transition;
+    fn draw(&mut self, _game: &mut Game, _ren: &RenderArgs, _c: Context, g: &mut G2d) {
+        clear([0.1, 0.1, 0.1, 1.0], g);
+    }
+    fn on_action(&mut self, _game: &mut Game, _action: Action) -> Transition {
+        Transition::None
+    }
+    fn on_press(&mut self, game: &mut Game, binding: Binding) {
+        game.controls.press(binding);
+    }
+    fn on_release(&mut self, game: &mut Game, binding: Binding) -> Transition {
+        fire(self, game, binding)
+    }
+    fn on_focus_lost(&mut self, _game: &mut Game) -> Transition {
+        Transition::None
+    }
+    /// Text typed on the keyboard, which also comes in as key presses and releases.
+    fn on_text(&mut self, _game: &mut Game, _text: &str) {}
+    fn is_overlay(&self) -> bool {
+        false
+    }
+}
+
+/// Runs the actions bound to a released key or button, stopping at the first one that changes scene.
+pub fn fire<S: Scene + ?Sized>(scene: &mut S, game: &mut Game, binding: Binding) -> Transition {
+    for action in game.controls.release(binding) {
+        match scene.on_action(game, action) {
+            Transition::None => {}
+            t => return t
+        }
+    }
+    Transition::None
+}
+
+// A fade out towards `next`, or a fade back in once it's `None`.
+struct Fade {
+    next: Option<Box<Scene>>,
+    time: f64
+}
+
+pub struct SceneStack {
+    scenes: Vec<Box<Scene>>,
+    fade: Option<Fade>
+}
+
+impl SceneStack {
+    pub fn new(first: Box<Scene>) -> SceneStack {
+        SceneStack { scenes: vec![first], fade: None }
+    }
+    // Input is dropped while fading out, since the scenes getting it are about to go.
+    fn fading_out(&self) -> bool {
+        match self.fade {
+            Some(ref fade) => fade.next.is_some(),
+            None => false
+        }
+    }
+    // The lowest scene that has to be drawn: the top one and any scenes showing through overlays.
+    fn first_visible(&self) -> usize {
+        self.scenes.iter().rposition(|s| !s.is_overlay()).unwrap_or(0)
+    }
+    fn apply(&mut self, transition: Transition) {
+        match transition {
+            Transition::None => {}
+            Transition::Push(scene) => self.scenes.push(scene),
+            // The bottom scene stays, there's always something to show.
+            Transition::Pop => {
+                if self.scenes.len() > 1 {
+                    self.scenes.pop();
+                }
+            }
+            Transition::Replace(scene) => {
+                self.scenes.pop();
+                self.scenes.push(scene);
+            }
+            Transition::FadeTo(scene) => {
+                if !self.fading_out() {
+                    self.fade = Some(Fade { next: Some(scene), time: 0.0 });
+                }
+            }
+        }
+    }
+    fn release(&mut self, game: &mut Game, binding: Binding) {
+        // Keys still have to come up, or they'd stay held in the next scene.
+        if self.fading_out() {
+            game.controls.release(binding);
+            return;
+        }
+        let t = self.scenes.last_mut().unwrap().on_release(game, binding);
+        self.apply(t);
+    }
+    pub fn update(&mut self, game: &mut Game, dt: f64, ui: &mut Ui) {
+        for binding in game.controls.poll() {
+            self.release(game, binding);
+        }
+        if let Some(mut fade) = self.fade.take() {
+            fade.time += dt;
+            if fade.time < FADE_TIME {
+                self.fade = Some(fade);
+            } else if let Some(next) = fade.next.take() {
+                self.scenes = vec![next];
+                self.fade = Some(Fade { next: None, time: 0.0 });
+            }
+        }
+        if !self.fading_out() {
+            let t = self.scenes.last_mut().unwrap().update(game, dt);
+            self.apply(t);
+        }
+        let first = self.first_visible();
+        let last = self.scenes.len() - 1;
+        let mut t = Transition::None;
+        {
+            let scenes = &mut self.scenes;
+            ui.set_widgets(|ui| {
+                for (i, scene) in scenes.iter_mut().enumerate().skip(first) {
+                    let st = scene.widgets(game, ui);
+                    if i == last {
+                        t = st;
+                    }
+                }
+            });
+        }
+        if !self.fading_out() {
+            self.apply(t);
+        }
+    }
+    pub fn draw(&mut self, game: &mut Game, ren: RenderArgs, e: PistonWindow, ui: &mut Ui) {
+        game.scx = (ren.width / 2) as f64;
+        game.scy = (ren.height / 2) as f64;
+        let first = self.first_visible();
+        let dark = match self.fade {
+            Some(ref fade) if fade.next.is_some() => fade.time / FADE_TIME,
+            Some(ref fade) => 1.0 - fade.time / FADE_TIME,
+            None => 0.0
+        };
+        let scenes = &mut self.scenes;
+        e.draw_2d(|c, g| {
+            for scene in scenes[first..].iter_mut() {
+                scene.draw(game, &ren, c, g);
+            }
+            ui.draw(c, g);
+            if dark > 0.0 {
+                rectangle([0.0, 0.0, 0.0, dark.min(1.0) as f32], [0.0, 0.0, ren.width as f64, ren.height as f64], c.transform, g);
+            }
+        });
+    }
+    pub fn on_input(&mut self, game: &mut Game, inp: Input) {
+        match inp {
+            Input::Move(Motion::MouseCursor(x, y)) => {
+                game.mx = x;
+                game.my = y;
+            }
+            Input::Press(b) => {
+                if let Some(b) = Binding::from_button(b) {
+                    if self.fading_out() {
+                        game.controls.press(b);
+                    } else {
+                        self.scenes.last_mut().unwrap().on_press(game, b);
+                    }
+                }
+            }
+            Input::Release(b) => {
+                if let Some(b) = Binding::from_button(b) {
+                    self.release(game, b);
+                }
+            }
+            Input::Text(ref text) if !self.fading_out() => self.scenes.last_mut().unwrap().on_text(game, text),
+            Input::Focus(false) => {
+                let t = self.scenes.last_mut().unwrap().on_focus_lost(game);
+                self.apply(t);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,1111 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use piston_window::{Context, G2d, Key, RenderArgs, Transformed, clear, ellipse, polygon, rectangle};
+use conrod::{self, Labelable, Widget};
+use conrod::Button as ButtonC;
+
+use controls::{Action, Binding, ACTIONS};
+use entities::Kind;
+use glyphs;
+use icons;
+use palette;
+use settings;
+use achievements::ACHIEVEMENTS;
+use simulation;
+use scene::{self, Scene, Transition};
+use Game;
+use Ui;
+use MAX_STEPS_PER_UPDATE;
+use new_seed;
+
+// The main menu's buttons, top to bottom.
+#[derive(Clone, Copy, PartialEq)]
+enum MenuItem {
+    Play,
+    Difficulty,
+    Seed,
+    HighScores,
+    Settings,
+    Credits,
+    Quit
+}
+
+const MENU: [MenuItem; 7] = [MenuItem::Play, MenuItem::Difficulty, MenuItem::Seed, MenuItem::HighScores,
+                             MenuItem::Settings, MenuItem::Credits, MenuItem::Quit];
+
+// The hidden level, which only its number key picks. Found by name, since custom levels can't take a built-in
+// one's.
+const SECRET_PROFILE: &'static str = "D0";
+
+// The game and its overlays are drawn together, so they share one set of widget ids.
+widget_ids!{
+    TIME,
+    GROWN,
+    TUTORIAL,
+    OBJECTS,
+    SCORE,
+    MESSAGE,
+    ACHIEVEMENT,
+    PAUSED,
+    RESUME,
+    RESTART,
+    SETTINGS,
+    QUIT,
+    CONFIRM,
+    ABANDON,
+    KEEP_PLAYING
+}
+
+pub struct MainMenu {
+    // The button picked with the keyboard or gamepad.
+    focus: usize
+}
+
+impl MainMenu {
+    pub fn new() -> MainMenu {
+        MainMenu { focus: 0 }
+    }
+    fn next_profile(&mut self, game: &mut Game, step: isize) {
+        let count = game.profiles.len() as isize;
+        loop {
+            game.menu_profile = ((game.menu_profile as isize + step + count) % count) as usize;
+            if game.profiles[game.menu_profile].name != SECRET_PROFILE {
+                break;
+            }
+        }
+    }
+    fn activate(&mut self, game: &mut Game, item: MenuItem) -> Transition {
+        match item {
+            MenuItem::Play => {
+                let profile = game.profiles[game.menu_profile].clone();
+                game.init(profile);
+                Transition::FadeTo(Box::new(Play))
+            }
+            MenuItem::Difficulty => {
+                self.next_profile(game, 1);
+                Transition::None
+            }
+            MenuItem::Seed => Transition::Push(Box::new(SeedEntry::new())),
+            MenuItem::HighScores => Transition::Push(Box::new(HighScores)),
+            MenuItem::Settings => Transition::Push(Box::new(Settings)),
+            MenuItem::Credits => Transition::Push(Box::new(Credits)),
+            MenuItem::Quit => Transition::Push(Box::new(ConfirmQuit)),
+        }
+    }
+}
+
+impl Scene for MainMenu {
+    fn widgets(&mut self, game: &mut Game, ui: &mut Ui) -> Transition {
+        use conrod::{color, Colorable, Positionable, Sizeable, Text};
+
+        // Generate a unique const `WidgetId` for each widget.
+        widget_ids!{
+            TEXT,
+            BUTTON with 7,
+            HINT,
+        }
+
+        let mut picked = None;
+
+        Text::new("Carket")
+        .color(color::white())
+        .mid_top_with_margin(60.0)
+        .set(TEXT, ui);
+
+        for (i, &item) in MENU.iter().enumerate() {
+            let label = match item {
+                MenuItem::Play => "Play".to_string(),
+                MenuItem::Difficulty => format!("< Difficulty: {} >", game.profiles[game.menu_profile].name),
+                MenuItem::Seed => format!("Seed: {}", game.seed),
+                MenuItem::HighScores => "High Scores".to_string(),
+                MenuItem::Settings => "Settings".to_string(),
+                MenuItem::Credits => "Credits".to_string(),
+                MenuItem::Quit => "Quit".to_string()
+            };
+            // The keyboard/gamepad focus stands out; conrod highlights the one under the mouse itself.
+            let colour = if i == self.focus { color::light_blue() } else { color::dark_charcoal() };
+            ButtonC::new()
+            .w_h(400.0, 45.0)
+            .down(if i == 0 { 40.0 } else { 12.0 })
+            .color(colour)
+            .label(&label)
+            .react(|| picked = Some(i))
+            .set(BUTTON + i, ui);
+        }
+
+        let hint = format!("Pick the seed to type one in, or {} for a new one\n{} / {} to choose, {} to pick, {} - Mute",
+                           game.controls.describe(Action::NewSeed),
+                           game.controls.describe(Action::MenuUp), game.controls.describe(Action::MenuDown),
+                           game.controls.describe(Action::Confirm), game.controls.describe(Action::Mute));
+        Text::new(&hint)
+        .color(color::white())
+        .down(30.0)
+        .align_text_middle()
+        .line_spacing(10.0)
+        .set(HINT, ui);
+
+        match picked {
+            Some(i) => {
+                self.focus = i;
+                self.activate(game, MENU[i])
+            }
+            None => Transition::None
+        }
+    }
+    fn on_action(&mut self, game: &mut Game, action: Action) -> Transition {
+        match action {
+            Action::Credits => return Transition::Push(Box::new(Credits)),
+            Action::HighScores => return Transition::Push(Box::new(HighScores)),
+            Action::Settings => return Transition::Push(Box::new(Settings)),
+            Action::Mute => game.toggle_mute(),
+            Action::Quit => return Transition::Push(Box::new(ConfirmQuit)),
+            Action::MenuUp => self.focus = (self.focus + MENU.len() - 1) % MENU.len(),
+            Action::MenuDown => self.focus = (self.focus + 1) % MENU.len(),
+            Action::MenuLeft if MENU[self.focus] == MenuItem::Difficulty => self.next_profile(game, -1),
+            Action::MenuRight if MENU[self.focus] == MenuItem::Difficulty => self.next_profile(game, 1),
+            Action::Confirm => {
+                let item = MENU[self.focus];
+                return self.activate(game, item);
+            }
+            Action::NewSeed => {
+                if !game.seed_locked {
+                    game.seed = new_seed();
+                }
+            }
+            Action::Difficulty(i) => {
+                let profile = game.profiles.get(i).cloned();
+                if let Some(profile) = profile {
+                    game.init(profile);
+                    return Transition::FadeTo(Box::new(Play));
+                }
+            }
+            _ => {}
+        }
+        Transition::None
+    }
+}
+
+/// The run itself, with the HUD on top.
+pub struct Play;
+
+impl Scene for Play {
+    fn update(&mut self, game: &mut Game, dt: f64) -> Transition {
+        game.music.update(dt, &game.sim);
+        game.hud.update(dt);
+        game.particles.update(dt);
+        game.achievements.update(dt);
+        game.accumulator += dt;
+        let mut steps = 0;
+        while game.accumulator >= simulation::STEP {
+            // Don't try to catch up after a long hitch, just drop the time.
+            if steps == MAX_STEPS_PER_UPDATE {
+                game.accumulator = 0.0;
+                break;
+            }
+            steps += 1;
+            game.accumulator -= simulation::STEP;
+            let (dt, input) = match game.next_tick(simulation::STEP) {
+                Some(tick) => tick,
+                None => return Transition::FadeTo(Box::new(End))
+            };
+            let events = game.sim.step(dt, &input);
+            game.publish(&events);
+            if !game.sim.alive {
+                game.record_score();
+                game.finish_run();
+                return Transition::FadeTo(Box::new(End));
+            }
+        }
+        Transition::None
+    }
+    fn widgets(&mut self, game: &mut Game, ui: &mut Ui) -> Transition {
+        use conrod::{color, Colorable, Positionable, Text};
+
+        let time = format!("Time Alive: {:.1}", game.sim.time_elapsed);
+
+        Text::new(&time[..])
+        .color(color::white())
+        .top_left()
+        .align_text_left()
+        .line_spacing(10.0)
+        .set(TIME, ui);
+
+        let mut grown = String::from_str("Grown: ").unwrap();
+        grown = grown + &(game.sim.grown).to_string()[..];
+
+        Text::new(&grown[..])
+        .color(color::white())
+        .top_right()
+        .align_text_left()
+        .line_spacing(10.0)
+        .set(GROWN, ui);
+
+        let steer = match &game.settings.controls[..] {
+            "Keyboard" => "Steer with the movement keys",
+            "Gamepad" => "Steer with the left stick",
+            _ => "Point your mouse where you want to move"
+        };
+        let point1 = format!("{}\nEat circles of your color to grow!", steer);
+        let point2 = format!("Collect triangles to gain powerups\nYou can use them to change your color instantly by pressing {} and {}!\nThey can also act as shields",
+                             game.controls.describe(Action::SwapLeft), game.controls.describe(Action::SwapRight));
+        if !game.settings.tutorial {
+        } else if game.sim.time_elapsed < 10.0 {
+            Text::new(&point1)
+            .color(color::white())
+            .middle()
+            .align_text_left()
+            .line_spacing(20.0)
+            .set(TUTORIAL, ui);
+        } else if game.sim.time_elapsed < 20.0 {
+            Text::new(&point2)
+            .color(color::white())
+            .middle()
+            .align_text_middle()
+            .line_spacing(10.0)
+            .set(TUTORIAL, ui);
+        } else if game.sim.time_elapsed < 30.0 && game.sim.profile.powerup_interval > 0.0 {
+            Text::new("Ringed discs are power-ups: slow time, magnet, colour bomb, invulnerability and growth\nA colour bomb clears every circle of its colour")
+            .color(color::white())
+            .middle()
+            .align_text_middle()
+            .line_spacing(10.0)
+            .set(TUTORIAL, ui);
+        }
+
+        let mut nro = String::from_str("Number of objects: ").unwrap();
+        nro = nro + &(game.sim.entities.len()).to_string()[..];
+        Text::new(&nro[..])
+        .color(color::white())
+        .bottom_right()
+        .align_text_left()
+        .line_spacing(10.0)
+        .set(OBJECTS, ui);
+
+        let score = format!("Score: {:.1}", game.sim.score);
+
+        Text::new(&score[..])
+        .color(color::white())
+        .mid_top()
+        .align_text_left()
+        .line_spacing(10.0)
+        .set(SCORE, ui);
+
+        if let Some(message) = game.hud.message() {
+            Text::new(message)
+            .color(color::white())
+            .mid_top_with_margin(40.0)
+            .set(MESSAGE, ui);
+        }
+
+        if let Some(name) = game.achievements.announcement() {
+            Text::new(&format!("Achievement unlocked: {}", name))
+            .color(color::yellow())
+            .mid_bottom_with_margin(60.0)
+            .set(ACHIEVEMENT, ui);
+        }
+
+        Transition::None
+    }
+    fn draw(&mut self, game: &mut Game, ren: &RenderArgs, c: Context, g: &mut G2d) {
+        let conrod::color::Rgba(rr, gg, bb, aa) = conrod::color::hsl(((((game.sim.time_elapsed * game.sim.time_elapsed / 10.0) as i64) % 628) as f32) / 100.0, 0.8, 0.1).to_rgb();
+        clear([rr, gg, bb, aa], g);
+        let rekt = [-game.sim.player.radius / 2.0, -game.sim.player.radius / 2.0, game.sim.player.radius, game.sim.player.radius];
+        let conrod::color::Rgba(rr, gg, bb, aa) = game.colours[game.sim.player.colour_state].to_rgb();
+        ellipse([rr, gg, bb, aa], rekt, c.transform.trans((ren.width / 2) as f64, (ren.height / 2) as f64).trans(game.sim.player.x, game.sim.player.y), g);
+        if game.settings.glyphs {
+            glyphs::draw(game.sim.player.colour_state, game.sim.player.radius / 3.0, c.transform.trans((ren.width / 2) as f64, (ren.height / 2) as f64).trans(game.sim.player.x, game.sim.player.y), g);
+        }
+        for (_, e) in game.sim.entities.iter() {
+            let conrod::color::Rgba(rr, gg, bb, aa) = game.colours[e.colour].to_rgb();
+            let at = c.transform.trans((ren.width / 2) as f64, (ren.height / 2) as f64).trans(e.x, e.y);
+            match e.kind {
+                Kind::Circle { inside_colour } => {
+                    let rekt = [-e.radius / 2.0, -e.radius / 2.0, e.radius, e.radius];
+                    ellipse([rr, gg, bb, aa], rekt, at, g);
+                    let rekt = [-e.radius / 4.0, -e.radius / 4.0, e.radius / 2.0, e.radius / 2.0];
+                    let conrod::color::Rgba(rr, gg, bb, aa) = game.colours[inside_colour].to_rgb();
+                    ellipse([rr, gg, bb, aa], rekt, at, g);
+                    // On the ring, above the inner dot.
+                    if game.settings.glyphs {
+                        glyphs::draw(e.colour, e.radius / 5.0, at.trans(0.0, -e.radius * 3.0 / 8.0), g);
+                    }
+                }
+                Kind::Triangle => {
+                    polygon([rr, gg, bb, aa], &[[0.0, -e.radius / 2.0], [e.radius / 2.0, e.radius / 2.0], [-e.radius / 2.0, e.radius / 2.0]], at, g);
+                    if game.settings.glyphs {
+                        glyphs::draw(e.colour, e.radius / 3.0, at.trans(0.0, e.radius / 6.0), g);
+                    }
+                }
+                Kind::PowerUp { power } => icons::draw(power, [rr, gg, bb, aa], e.radius, at, g)
+            }
+        }
+        if game.sim.col_left != None {
+            let conrod::color::Rgba(rr, gg, bb, aa) = game.colours[game.sim.col_left.unwrap()].to_rgb();
+            polygon([rr, gg, bb, aa], &[[0.0, -60.0 / 2.0], [60.0 / 2.0, 60.0 / 2.0], [-60.0 / 2.0, 60.0 / 2.0]], c.transform.trans(50.0, 50.0), g);
+            if game.settings.glyphs {
+                glyphs::draw(game.sim.col_left.unwrap(), 20.0, c.transform.trans(50.0, 60.0), g);
+            }
+        }
+        if game.sim.col_right != None {
+            let conrod::color::Rgba(rr, gg, bb, aa) = game.colours[game.sim.col_right.unwrap()].to_rgb();
+            polygon([rr, gg, bb, aa], &[[0.0, -60.0 / 2.0], [60.0 / 2.0, 60.0 / 2.0], [-60.0 / 2.0, 60.0 / 2.0]], c.transform.trans(ren.width as f64 - 50.0, 50.0), g);
+            if game.settings.glyphs {
+                glyphs::draw(game.sim.col_right.unwrap(), 20.0, c.transform.trans(ren.width as f64 - 50.0, 60.0), g);
+            }
+        }
+        game.particles.draw(&game.colours, c.transform.trans((ren.width / 2) as f64, (ren.height / 2) as f64), g);
+        // The powers still going, under the left triangle slot, with how long each has left.
+        for (i, effect) in game.sim.effects.iter().enumerate() {
+            let (x, y) = (40.0 + i as f64 * 45.0, 120.0);
+            icons::draw(effect.power, [1.0, 1.0, 1.0, 1.0], 32.0, c.transform.trans(x, y), g);
+            let left = effect.time_left / effect.power.duration().unwrap_or(effect.time_left);
+            rectangle([1.0, 1.0, 1.0, 0.8], [x - 18.0, y + 22.0, 36.0 * left, 4.0], c.transform, g);
+        }
+        // The colours in play along the bottom, with the player's own one bigger.
+        for (i, colour) in game.colours.iter().enumerate() {
+            let conrod::color::Rgba(rr, gg, bb, aa) = colour.to_rgb();
+            let size = if i == game.sim.player.colour_state { 24.0 } else { 14.0 };
+            let (x, y) = (30.0 + i as f64 * 30.0, ren.height as f64 - 30.0);
+            ellipse([rr, gg, bb, aa], [-size / 2.0, -size / 2.0, size, size], c.transform.trans(x, y), g);
+            if game.settings.glyphs {
+                glyphs::draw(i, size / 2.0, c.transform.trans(x, y), g);
+            }
+        }
+    }
+    fn on_action(&mut self, game: &mut Game, action: Action) -> Transition {
+        match action {
+            Action::SwapLeft | Action::SwapRight => game.swaps.push_back(action),
+            Action::Pause => return pause(game, Box::new(Pause::new())),
+            Action::Mute => game.toggle_mute(),
+            // Back asks before giving up on the run.
+            Action::Back => return pause(game, Box::new(ConfirmAbandon)),
+            _ => {}
+        }
+        Transition::None
+    }
+    // Don't let the player die while they're in another window.
+    fn on_focus_lost(&mut self, game: &mut Game) -> Transition {
+        pause(game, Box::new(Pause::new()))
+    }
+}
+
+// Stops the run under an overlay. Swaps still waiting for a tick are dropped rather than played on resume.
+fn pause(game: &mut Game, overlay: Box<Scene>) -> Transition {
+    game.swaps.clear();
+    Transition::Push(overlay)
+}
+
+// Dims the game under an overlay.
+fn dim(ren: &RenderArgs, c: Context, g: &mut G2d) {
+    rectangle([0.0, 0.0, 0.0, 0.6], [0.0, 0.0, ren.width as f64, ren.height as f64], c.transform, g);
+}
+
+// The pause menu's buttons, top to bottom.
+#[derive(Clone, Copy, PartialEq)]
+enum PauseItem {
+    Resume,
+    Restart,
+    Settings,
+    Quit
+}
+
+const PAUSE_MENU: [PauseItem; 4] = [PauseItem::Resume, PauseItem::Restart, PauseItem::Settings, PauseItem::Quit];
+
+pub struct Pause {
+    // The button picked with the keyboard or gamepad.
+    focus: usize
+}
+
+impl Pause {
+    pub fn new() -> Pause {
+        Pause { focus: 0 }
+    }
+    fn activate(&mut self, game: &mut Game, item: PauseItem) -> Transition {
+        match item {
+            PauseItem::Resume => Transition::Pop,
+            PauseItem::Restart => {
+                game.restart();
+                Transition::Pop
+            }
+            PauseItem::Settings => Transition::Push(Box::new(Settings)),
+            PauseItem::Quit => Transition::Replace(Box::new(ConfirmAbandon))
+        }
+    }
+}
+
+impl Scene for Pause {
+    fn widgets(&mut self, game: &mut Game, ui: &mut Ui) -> Transition {
+        use conrod::{color, Colorable, Positionable, Sizeable, Text};
+
+        let mut picked = None;
+
+        Text::new("Paused")
+        .color(color::white())
+        .xy([0.0, 140.0])
+        .set(PAUSED, ui);
+
+        for (i, &item) in PAUSE_MENU.iter().enumerate() {
+            let (label, id) = match item {
+                PauseItem::Resume => ("Resume", RESUME),
+                PauseItem::Restart => ("Restart", RESTART),
+                PauseItem::Settings => ("Settings", SETTINGS),
+                PauseItem::Quit => ("Quit to Menu", QUIT)
+            };
+            let colour = if i == self.focus { color::light_blue() } else { color::dark_charcoal() };
+            ButtonC::new()
+            .w_h(300.0, 40.0)
+            .down(if i == 0 { 30.0 } else { 15.0 })
+            .color(colour)
+            .label(label)
+            .react(|| picked = Some(i))
+            .set(id, ui);
+        }
+
+        match picked {
+            Some(i) => {
+                self.focus = i;
+                self.activate(game, PAUSE_MENU[i])
+            }
+            None => Transition::None
+        }
+    }
+    fn draw(&mut self, _game: &mut Game, ren: &RenderArgs, c: Context, g: &mut G2d) {
+        dim(ren, c, g);
+    }
+    fn on_action(&mut self, game: &mut Game, action: Action) -> Transition {
+        match action {
+            Action::Pause => return Transition::Pop,
+            Action::Mute => game.toggle_mute(),
+            Action::Back => return Transition::Replace(Box::new(ConfirmAbandon)),
+            Action::MenuUp => self.focus = (self.focus + PAUSE_MENU.len() - 1) % PAUSE_MENU.len(),
+            Action::MenuDown => self.focus = (self.focus + 1) % PAUSE_MENU.len(),
+            Action::Confirm => {
+                let item = PAUSE_MENU[self.focus];
+                return self.activate(game, item);
+            }
+            _ => {}
+        }
+        Transition::None
+    }
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+/// Asks whether to give up on the current run.
+pub struct ConfirmAbandon;
+
+impl Scene for ConfirmAbandon {
+    fn widgets(&mut self, game: &mut Game, ui: &mut Ui) -> Transition {
+        use conrod::{color, Colorable, Positionable, Sizeable, Text};
+
+        let (mut abandon, mut keep_playing) = (false, false);
+
+        Text::new("Abandon this run?\nYour score still counts for the high score table")
+        .color(color::white())
+        .xy([0.0, 80.0])
+        .align_text_middle()
+        .set(CONFIRM, ui);
+
+        ButtonC::new()
+        .w_h(300.0, 40.0)
+        .down(30.0)
+        .label("Abandon run")
+        .react(|| abandon = true)
+        .set(ABANDON, ui);
+
+        ButtonC::new()
+        .w_h(300.0, 40.0)
+        .down(15.0)
+        .label("Keep playing")
+        .react(|| keep_playing = true)
+        .set(KEEP_PLAYING, ui);
+
+        if abandon {
+            game.abandon();
+            Transition::FadeTo(Box::new(End))
+        } else if keep_playing {
+            Transition::Pop
+        } else {
+            Transition::None
+        }
+    }
+    fn draw(&mut self, _game: &mut Game, ren: &RenderArgs, c: Context, g: &mut G2d) {
+        dim(ren, c, g);
+    }
+    fn on_action(&mut self, game: &mut Game, action: Action) -> Transition {
+        match action {
+            // A second Back cancels.
+            Action::Back => Transition::Pop,
+            Action::Confirm => {
+                game.abandon();
+                Transition::FadeTo(Box::new(End))
+            }
+            _ => Transition::None
+        }
+    }
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+/// Lets the player type in a seed to play, e.g. one from someone else's high score.
+pub struct SeedEntry {
+    text: String,
+    invalid: bool
+}
+
+impl SeedEntry {
+    pub fn new() -> SeedEntry {
+        SeedEntry { text: String::new(), invalid: false }
+    }
+    fn submit(&mut self, game: &mut Game) -> Transition {
+        match self.text.parse::<usize>() {
+            Ok(seed) => {
+                game.seed = seed;
+                Transition::Pop
+            }
+            Err(_) => {
+                self.invalid = true;
+                Transition::None
+            }
+        }
+    }
+}
+
+impl Scene for SeedEntry {
+    fn widgets(&mut self, game: &mut Game, ui: &mut Ui) -> Transition {
+        use conrod::{color, Colorable, Positionable, Sizeable, Text};
+
+        // Generate a unique const `WidgetId` for each widget.
+        widget_ids!{
+            TITLE,
+            SEED,
+            USE,
+            CANCEL,
+        }
+
+        let (mut submit, mut cancel) = (false, false);
+
+        let title = if self.invalid {
+            "That isn't a seed, seeds are whole numbers".to_string()
+        } else {
+            format!("Type a seed, then press {}", game.controls.describe(Action::Confirm))
+        };
+        Text::new(&title)
+        .color(color::white())
+        .xy([0.0, 120.0])
+        .align_text_middle()
+        .set(TITLE, ui);
+
+        Text::new(&format!("{}_", self.text))
+        .color(color::white())
+        .down(30.0)
+        .align_text_middle()
+        .set(SEED, ui);
+
+        ButtonC::new()
+        .w_h(300.0, 40.0)
+        .down(30.0)
+        .label("Use seed")
+        .react(|| submit = true)
+        .set(USE, ui);
+
+        ButtonC::new()
+        .w_h(300.0, 40.0)
+        .down(15.0)
+        .label("Cancel")
+        .react(|| cancel = true)
+        .set(CANCEL, ui);
+
+        if submit {
+            self.submit(game)
+        } else if cancel {
+            Transition::Pop
+        } else {
+            Transition::None
+        }
+    }
+    fn on_action(&mut self, game: &mut Game, action: Action) -> Transition {
+        match action {
+            Action::Confirm => self.submit(game),
+            Action::Back => Transition::Pop,
+            _ => Transition::None
+        }
+    }
+    fn on_release(&mut self, game: &mut Game, binding: Binding) -> Transition {
+        if binding == Binding::Key(Key::Backspace) {
+            self.text.pop();
+            return Transition::None;
+        }
+        scene::fire(self, game, binding)
+    }
+    // Digits only, and no more than a seed can have.
+    fn on_text(&mut self, _game: &mut Game, text: &str) {
+        for c in text.chars() {
+            if c.is_digit(10) && self.text.len() < 20 {
+                self.text.push(c);
+                self.invalid = false;
+            }
+        }
+    }
+}
+
+/// Asks whether to close the game, so a stray key or click on the main menu can't.
+pub struct ConfirmQuit;
+
+impl Scene for ConfirmQuit {
+    fn widgets(&mut self, game: &mut Game, ui: &mut Ui) -> Transition {
+        use conrod::{color, Colorable, Positionable, Sizeable, Text};
+
+        // Generate a unique const `WidgetId` for each widget.
+        widget_ids!{
+            TEXT,
+            QUIT,
+            CANCEL,
+        }
+
+        let (mut quit, mut cancel) = (false, false);
+
+        Text::new("Quit Carket?")
+        .color(color::white())
+        .xy([0.0, 80.0])
+        .align_text_middle()
+        .set(TEXT, ui);
+
+        ButtonC::new()
+        .w_h(300.0, 40.0)
+        .down(30.0)
+        .label("Quit")
+        .react(|| quit = true)
+        .set(QUIT, ui);
+
+        ButtonC::new()
+        .w_h(300.0, 40.0)
+        .down(15.0)
+        .label("Cancel")
+        .react(|| cancel = true)
+        .set(CANCEL, ui);
+
+        if quit {
+            game.quit = true;
+            Transition::None
+        } else if cancel {
+            Transition::Pop
+        } else {
+            Transition::None
+        }
+    }
+    fn on_action(&mut self, game: &mut Game, action: Action) -> Transition {
+        match action {
+            Action::Back => Transition::Pop,
+            Action::Confirm => {
+                game.quit = true;
+                Transition::None
+            }
+            _ => Transition::None
+        }
+    }
+}
+
+/// Goes back to whatever opened it, the main menu or the pause menu.
+pub struct Settings;
+
+impl Scene for Settings {
+    fn widgets(&mut self, game: &mut Game, ui: &mut Ui) -> Transition {
+        use conrod::{color, Colorable, Positionable, Sizeable, Slider, Text, Toggle};
+
+        // Generate a unique const `WidgetId` for each widget.
+        widget_ids!{
+            TITLE,
+            MASTER,
+            MUSIC,
+            SFX,
+            MUTE,
+            RESOLUTION,
+            FULLSCREEN,
+            PALETTE,
+            GLYPHS,
+            CONTROLS,
+            REBIND,
+            TUTORIAL,
+            BACK,
+            NOTE,
+        }
+
+        let mut opts = game.settings.clone();
+        let mut back = false;
+        let mut rebind = false;
+
+        Text::new("Settings")
+        .color(color::white())
+        .mid_top_with_margin(40.0)
+        .set(TITLE, ui);
+
+        let master = opts.master_volume;
+        Slider::new(master, 0.0, 1.0)
+        .w_h(400.0, 40.0)
+        .down(30.0)
+        .label("Master volume")
+        .react(|v| opts.master_volume = v)
+        .set(MASTER, ui);
+
+        let music = opts.music_volume;
+        Slider::new(music, 0.0, 1.0)
+        .w_h(400.0, 40.0)
+        .down(15.0)
+        .label("Music volume")
+        .react(|v| opts.music_volume = v)
+        .set(MUSIC, ui);
+
+        let sfx = opts.sfx_volume;
+        Slider::new(sfx, 0.0, 1.0)
+        .w_h(400.0, 40.0)
+        .down(15.0)
+        .label("Sound effects volume")
+        .react(|v| opts.sfx_volume = v)
+        .set(SFX, ui);
+
+        let resolution_label = format!("Resolution: {}x{}", opts.width, opts.height);
+        ButtonC::new()
+        .w_h(400.0, 40.0)
+        .down(30.0)
+        .label(&resolution_label)
+        .react(|| opts.next_resolution())
+        .set(RESOLUTION, ui);
+
+        let fullscreen = opts.fullscreen;
+        Toggle::new(fullscreen)
+        .w_h(400.0, 40.0)
+        .down(15.0)
+        .label("Fullscreen")
+        .react(|v| opts.fullscreen = v)
+        .set(FULLSCREEN, ui);
+
+        let palette_label = format!("Colours: {}", opts.palette);
+        ButtonC::new()
+        .w_h(400.0, 40.0)
+        .down(30.0)
+        .label(&palette_label)
+        .react(|| opts.palette = settings::cycle(&palette::NAMES, &opts.palette))
+        .set(PALETTE, ui);
+
+        let glyphs = opts.glyphs;
+        Toggle::new(glyphs)
+        .w_h(400.0, 40.0)
+        .down(15.0)
+        .label("Colour shapes")
+        .react(|v| opts.glyphs = v)
+        .set(GLYPHS, ui);
+
+        let controls_label = format!("Controls: {}", opts.controls);
+        ButtonC::new()
+        .w_h(400.0, 40.0)
+        .down(15.0)
+        .label(&controls_label)
+        .react(|| opts.controls = settings::cycle(&settings::CONTROL_SCHEMES, &opts.controls))
+        .set(CONTROLS, ui);
+
+        ButtonC::new()
+        .w_h(400.0, 40.0)
+        .down(15.0)
+        .label("Rebind keys")
+        .react(|| rebind = true)
+        .set(REBIND, ui);
+
+        let muted = opts.muted;
+        Toggle::new(muted)
+        .w_h(400.0, 40.0)
+        .down(15.0)
+        .label("Mute")
+        .react(|v| opts.muted = v)
+        .set(MUTE, ui);
+
+        let tutorial = opts.tutorial;
+        Toggle::new(tutorial)
+        .w_h(400.0, 40.0)
+        .down(15.0)
+        .label("Tutorial hints")
+        .react(|v| opts.tutorial = v)
+        .set(TUTORIAL, ui);
+
+        ButtonC::new()
+        .w_h(400.0, 40.0)
+        .down(30.0)
+        .label("Back")
+        .react(|| back = true)
+        .set(BACK, ui);
+
+        Text::new("Resolution and fullscreen apply the next time the game starts")
+        .color(color::white())
+        .down(15.0)
+        .set(NOTE, ui);
+
+        game.settings = opts;
+        game.audio.set_volumes(&game.settings);
+        if back {
+            game.save_settings();
+            Transition::Pop
+        } else if rebind {
+            game.save_settings();
+            Transition::Push(Box::new(Controls::new()))
+        } else {
+            Transition::None
+        }
+    }
+    fn on_action(&mut self, game: &mut Game, action: Action) -> Transition {
+        match action {
+            Action::Confirm | Action::Back => {
+                game.save_settings();
+                Transition::Pop
+            }
+            _ => Transition::None
+        }
+    }
+}
+
+/// The rebinding screen.
+pub struct Controls {
+    // The action waiting for a new key.
+    rebinding: Option<Action>,
+    // Why the last binding was turned down, if it was.
+    refused: Option<String>
+}
+
+impl Controls {
+    pub fn new() -> Controls {
+        Controls { rebinding: None, refused: None }
+    }
+}
+
+impl Scene for Controls {
+    fn widgets(&mut self, game: &mut Game, ui: &mut Ui) -> Transition {
+        use conrod::{color, Colorable, Positionable, Sizeable, Text};
+
+        // Generate a unique const `WidgetId` for each widget.
+        widget_ids!{
+            TITLE,
+            RESET,
+            BACK,
+            ACTION with 29,
+        }
+
+        let mut picked = None;
+        let mut reset = false;
+        let mut back = false;
+
+        let title = match self.refused {
+            Some(ref refused) => format!("Controls\n{}", refused),
+            None => "Controls\nClick an action, then press the key or button to bind to it".to_string()
+        };
+        Text::new(&title)
+        .color(color::white())
+        .mid_top_with_margin(20.0)
+        .align_text_middle()
+        .set(TITLE, ui);
+
+        // Two columns of buttons, one per action.
+        let rows = (ACTIONS.len() + 1) / 2;
+        for (i, &action) in ACTIONS.iter().enumerate() {
+            let bound = if self.rebinding == Some(action) {
+                "press a key...".to_string()
+            } else {
+                game.controls.describe(action)
+            };
+            let label = format!("{}: {}", action.name().replace("_", " "), bound);
+            ButtonC::new()
+            .w_h(360.0, 28.0)
+            .top_left_with_margins(80.0 + (i % rows) as f64 * 32.0, 20.0 + (i / rows) as f64 * 380.0)
+            .label(&label)
+            .react(|| picked = Some(action))
+            .set(ACTION + i, ui);
+        }
+
+        ButtonC::new()
+        .w_h(400.0, 40.0)
+        .mid_bottom_with_margin(75.0)
+        .label("Reset to defaults")
+        .react(|| reset = true)
+        .set(RESET, ui);
+
+        ButtonC::new()
+        .w_h(400.0, 40.0)
+        .down(15.0)
+        .label("Back")
+        .react(|| back = true)
+        .set(BACK, ui);
+
+        if picked.is_some() {
+            self.rebinding = picked;
+            self.refused = None;
+        }
+        if reset {
+            self.rebinding = None;
+            self.refused = None;
+            game.settings.bindings = None;
+            game.controls.reload(&game.settings);
+        }
+        if back {
+            game.save_settings();
+            return Transition::Pop;
+        }
+        Transition::None
+    }
+    fn on_action(&mut self, game: &mut Game, action: Action) -> Transition {
+        match action {
+            Action::Confirm | Action::Back => {
+                game.save_settings();
+                Transition::Pop
+            }
+            _ => Transition::None
+        }
+    }
+    fn on_press(&mut self, game: &mut Game, binding: Binding) {
+        if self.rebinding.is_none() {
+            game.controls.press(binding);
+        }
+    }
+    // A key or button was let go: it's either the new binding being waited for, or it fires its actions.
+    fn on_release(&mut self, game: &mut Game, binding: Binding) -> Transition {
+        if let Some(action) = self.rebinding.take() {
+            // One key doing two things at once is never what the player wants, so they have to free it up first.
+            let clashes = game.controls.clashes(action, binding);
+            if clashes.len() > 0 {
+                let names: Vec<String> = clashes.iter().map(|a| a.name().replace("_", " ")).collect();
+                self.refused = Some(format!("{} is already bound to {}", binding.name(), names.join(", ")));
+                return Transition::None;
+            }
+            if game.settings.bindings.is_none() {
+                game.settings.bindings = Some(BTreeMap::new());
+            }
+            if let Some(ref mut bindings) = game.settings.bindings {
+                bindings.insert(action.name(), vec![binding.name()]);
+            }
+            game.controls.reload(&game.settings);
+            return Transition::None;
+        }
+        scene::fire(self, game, binding)
+    }
+}
+
+pub struct Credits;
+
+impl Scene for Credits {
+    fn widgets(&mut self, game: &mut Game, ui: &mut Ui) -> Transition {
+        use conrod::{color, Colorable, Positionable, Text};
+
+        // Generate a unique const `WidgetId` for each widget.
+        widget_ids!{
+            TEXT,
+        }
+
+        let text = "Carket\n\nCoded by: StelarCF\n\nSpecial Thanks To:\nWindowsBunny from irc.mozilla.org #rust_gamedev for building my game for Windows\nMysticPing for some useful ideas\nCziken20 for giving me a couple of seconds of thinking my stream wasn't empty\n\nPress ".to_string() + &game.controls.describe(Action::Confirm) + " to go back";
+
+        Text::new(&text)
+        .color(color::white())
+        .middle()
+        .align_text_left()
+        .line_spacing(10.0)
+        .set(TEXT, ui);
+
+        Transition::None
+    }
+    fn on_action(&mut self, _game: &mut Game, action: Action) -> Transition {
+        match action {
+            Action::Confirm | Action::Back => Transition::Pop,
+            _ => Transition::None
+        }
+    }
+}
+
+pub struct HighScores;
+
+impl Scene for HighScores {
+    fn widgets(&mut self, game: &mut Game, ui: &mut Ui) -> Transition {
+        use conrod::{color, Colorable, Positionable, Text};
+
+        // Generate a unique const `WidgetId` for each widget.
+        widget_ids!{
+            TEXT,
+        }
+
+        let mut text = String::from_str("High Scores\n").unwrap();
+        for p in &game.profiles {
+            text = text + "\n" + &p.name + "\n";
+            let table = game.high_scores.table(&p.name);
+            if table.len() == 0 {
+                text = text + "  -\n";
+            }
+            for (i, e) in table.iter().enumerate() {
+                text = text + &format!("  {}. {:.1}  (time {:.1}, grown {}, {})\n", i + 1, e.score, e.time_alive, e.grown, e.date);
+            }
+        }
+        let stats = &game.stats;
+        text = text + &format!("\nAll runs: {} circles eaten ({} on the beat), {} triangles, {} shields lost, {} deaths, best combo x{}\n",
+                               stats.circles_eaten, stats.on_beat, stats.triangles_picked, stats.shields_lost, stats.deaths, stats.best_combo);
+        text = text + "\nAchievements\n";
+        for a in ACHIEVEMENTS.iter() {
+            let mark = if game.achievements.is_unlocked(a.id) { "[x]" } else { "[ ]" };
+            text = text + &format!("  {} {} - {}\n", mark, a.name, a.description);
+        }
+        text = text + "\nPress " + &game.controls.describe(Action::Confirm) + " to go back";
+
+        Text::new(&text[..])
+        .color(color::white())
+        .middle()
+        .align_text_left()
+        .line_spacing(5.0)
+        .set(TEXT, ui);
+
+        Transition::None
+    }
+    fn on_action(&mut self, _game: &mut Game, action: Action) -> Transition {
+        match action {
+            Action::Confirm | Action::Back => Transition::Pop,
+            _ => Transition::None
+        }
+    }
+}
+
+/// The score screen after a run, whether it ended by dying or was given up on.
+pub struct End;
+
+impl Scene for End {
+    fn widgets(&mut self, game: &mut Game, ui: &mut Ui) -> Transition {
+        use conrod::{color, Colorable, Positionable, Text};
+
+        // Generate a unique const `WidgetId` for each widget.
+        widget_ids!{
+            TEXT,
+        }
+
+        let mut score = format!("{}\n Your score was: {:.1}\nSeed: {}", if game.sim.alive { "Run abandoned" } else { "You died" },
+                                game.sim.score, game.sim.seed);
+        if let Some(rank) = game.last_rank {
+            score = score + "\n\nNew high score! #" + &(rank + 1).to_string() + " on " + &game.sim.profile.name;
+        }
+        score = score + "\n\nPress " + &game.controls.describe(Action::Confirm) + " to go back to the main menu";
+
+        Text::new(&score[..])
+        .color(color::white())
+        .middle()
+        .align_text_middle()
+        .line_spacing(20.0)
+        .set(TEXT, ui);
+
+        Transition::None
+    }
+    fn draw(&mut self, _game: &mut Game, _ren: &RenderArgs, _c: Context, g: &mut G2d) {
+        clear([0.2, 0.1, 0.1, 1.0], g);
+    }
+    fn on_action(&mut self, game: &mut Game, action: Action) -> Transition {
+        match action {
+            Action::Confirm => {
+                game.back_to_menu();
+                Transition::FadeTo(Box::new(MainMenu::new()))
+            }
+            _ => Transition::None
+        }
+    }
+}
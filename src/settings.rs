@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use toml;
+
+use paths;
+
+/// The resolutions offered in the settings screen; others can still be set in the file.
+pub const RESOLUTIONS: [(u32, u32); 6] = [(800, 600), (1024, 768), (1080, 1080), (1280, 720), (1600, 900), (1920, 1080)];
+
+/// Mouse steers towards the cursor, the others steer in a direction.
+pub const CONTROL_SCHEMES: [&'static str; 3] = ["Mouse", "Keyboard", "Gamepad"];
+
+/// Player options, stored as TOML in the data dir. Window options take effect on the next start.
+#[derive(Clone, RustcEncodable, RustcDecodable)]
+pub struct Settings {
+    pub width: u32,
+    pub height: u32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    pub samples: u8,
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub muted: bool,
+    pub palette: String,
+    // Draw a shape for each colour on top of it, for players who can't rely on hue.
+    pub glyphs: bool,
+    pub controls: String,
+    pub tutorial: bool,
+    // Actions bound to something other than the control scheme's defaults, e.g. `pause = ["Key Space"]`.
+    pub bindings: Option<BTreeMap<String, Vec<String>>>
+}
+
+impl Settings {
+    pub fn new() -> Settings {
+        Settings { width: 1080, height: 1080, fullscreen: false, vsync: true, samples: 4,
+                   master_volume: 1.0, music_volume: 1.0, sfx_volume: 1.0, muted: false,
+                   palette: "Classic".to_string(), glyphs: false, controls: "Mouse".to_string(), tutorial: true,
+                   bindings: None }
+    }
+    pub fn next_resolution(&mut self) {
+        let i = RESOLUTIONS.iter().position(|&r| r == (self.width, self.height)).map_or(0, |i| (i + 1) % RESOLUTIONS.len());
+        self.width = RESOLUTIONS[i].0;
+        self.height = RESOLUTIONS[i].1;
+    }
+    /// Falls back to the defaults if the file is missing or can't be read, and for options it doesn't mention
+    /// (e.g. ones added since it was saved).
+    pub fn load(path: &Path) -> Settings {
+        paths::load_toml_with(path, "settings", decode).unwrap_or(Settings::new())
+    }
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        paths::save_toml(path, self)
+    }
+}
+
+fn decode(table: toml::Table) -> Option<Settings> {
+    toml::decode(with_defaults(table))
+}
+
+// Every option missing from the file gets its default, so files saved by any earlier version still load.
+fn with_defaults(mut table: toml::Table) -> toml::Value {
+    if let toml::Value::Table(defaults) = toml::encode(&Settings::new()) {
+        for (key, value) in defaults {
+            if !table.contains_key(&key) {
+                table.insert(key, value);
+            }
+        }
+    }
+    toml::Value::Table(table)
+}
+
+/// The option after `current` in `options`, wrapping around.
+pub fn cycle(options: &[&str], current: &str) -> String {
+    let i = options.iter().position(|&o| o == current).map_or(0, |i| (i + 1) % options.len());
+    options[i].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use toml;
+
+    use super::decode;
+
+    #[test]
+    fn reads_files_from_before_the_settings_screen() {
+        let table = toml::Parser::new("width = 800\nheight = 600\nfullscreen = true\nvsync = false\nsamples = 0\n").parse();
+        let settings = decode(table.unwrap()).unwrap();
+        assert_eq!((settings.width, settings.height, settings.fullscreen), (800, 600, true));
+        assert_eq!(settings.master_volume, 1.0);
+        assert_eq!(settings.palette, "Classic");
+        assert!(settings.tutorial);
+        assert!(settings.bindings.is_none());
+    }
+}
@@ -0,0 +1,578 @@
+use nalgebra::{Iso2, Norm};
+use ncollide::geometry;
+use ncollide::shape::{Ball, Triangle as TriangleShape};
+
+use rand::{Isaac64Rng, Rng, SeedableRng};
+
+use {Vec1, Vec2, Pnt2};
+use broadphase::{Grid, swept_box};
+use profile::DifficultyProfile;
+use clock::{self, BeatClock, BEATS_PER_BAR};
+use director::SpawnDirector;
+use entities::{Entities, Entity, Kind};
+use powerups::{self, Effect, Power, POWERS};
+
+/// The simulation always advances in steps of this many seconds, whatever the frame rate.
+pub const STEP: f64 = 1.0 / 120.0;
+
+/// How fast the player moves towards its target, in pixels per second.
+pub const PLAYER_SPEED: f64 = 500.0;
+
+// How far outside the visible playfield circles appear, and how far they can get before they're removed.
+const SPAWN_MARGIN: f64 = 420.0;
+const DESPAWN_MARGIN: f64 = 660.0;
+
+// Most circles that can join in on a single beat.
+const MAX_WAVE: usize = 4;
+
+// Eating a circle within this fraction of a beat of the beat itself scores `RHYTHM_BONUS` times as much.
+const RHYTHM_WINDOW: f64 = 0.15;
+const RHYTHM_BONUS: f64 = 1.5;
+
+// Size of the broad phase grid cells; a bit bigger than the largest the player can grow.
+const CELL_SIZE: f64 = 256.0;
+
+fn at(p: Vec2) -> Iso2<f64> {
+    Iso2::new(p, Vec1::new(0.0))
+}
+
+// Shapes move in straight lines during a step, so this catches fast circles that would skip past the player.
+// `radius` is really a diameter everywhere in the game, hence the halving. `speed` is how fast time ran for the entity.
+fn hits(p0: Vec2, pv: Vec2, player: &Player, e: &Entity, speed: f64, dt: f64) -> bool {
+    let ev = Vec2::new(e.vx, e.vy) * speed;
+    let e0 = Vec2::new(e.x, e.y) - ev * dt;
+    let ball = Ball::new(player.radius / 2.0);
+    let r = e.radius / 2.0;
+    let toi = match e.kind {
+        Kind::Circle { .. } | Kind::PowerUp { .. } => geometry::time_of_impact(&at(p0), &pv, &ball, &at(e0), &ev, &Ball::new(r)),
+        Kind::Triangle => {
+            let shape = TriangleShape::new(Pnt2::new(0.0, -r), Pnt2::new(r, r), Pnt2::new(-r, r));
+            geometry::time_of_impact(&at(p0), &pv, &ball, &at(e0), &ev, &shape)
+        }
+    };
+    toi.map_or(false, |toi| toi <= dt)
+}
+
+pub struct Player {
+    pub x: f64, pub y: f64,
+    pub radius: f64,
+    pub colour_state: usize
+}
+
+impl Player {
+    pub fn new() -> Player {
+        Player { x: 0.0, y: 0.0, radius: 40.0, colour_state: 0 }
+    }
+}
+
+/// A circle coming in from just outside the playfield, headed roughly for the middle.
+pub fn new_circle(rng: &mut Isaac64Rng, outside_colour: usize, colours: usize, speed_multi: f64, half_width: f64, half_height: f64) -> Entity {
+    let (x, y);
+    let (sx, sy) = (half_width + SPAWN_MARGIN, half_height + SPAWN_MARGIN);
+    if rng.gen() { // top or bottom
+        if rng.gen() { // top
+            y = -sy;
+            x = rng.gen::<f64>() * sx * 2.0 - sx;
+        } else {
+            y = sy;
+            x = rng.gen::<f64>() * sx * 2.0 - sx;
+        }
+    } else { // left or right
+        if rng.gen() { // left
+            x = -sx;
+            y = rng.gen::<f64>() * sy * 2.0 - sy;
+        } else {
+            x = sx;
+            y = rng.gen::<f64>() * sy * 2.0 - sy;
+        }
+    }
+    let n = Vec2::new(x, y).norm();
+    let mut vy = -y / n;
+    let mut vx = -x / n;
+    let speed = speed_multi * 100.0;
+    vy *= speed * (rng.gen::<f64>() + 0.5);
+    vx *= speed * (rng.gen::<f64>() + 0.5);
+    let color_out = rng.gen::<u32>() as usize % colours;
+    Entity {
+        kind: Kind::Circle { inside_colour: color_out },
+        x: x, y: y,
+        vx: vx, vy: vy,
+        radius: 40.0,
+        colour: outside_colour,
+        lifetime: None
+    }
+}
+
+pub fn new_triangle(rng: &mut Isaac64Rng, colours: usize, px: f64, py: f64) -> Entity {
+    Entity {
+        kind: Kind::Triangle,
+        x: px, y: py,
+        vx: 0.0, vy: 0.0,
+        radius: 20.0,
+        colour: rng.gen::<u32>() as usize % colours,
+        lifetime: Some(30.0)
+    }
+}
+
+/// A triangle somewhere in the `bx` by `by` box around the middle of the playfield.
+pub fn new_triangle_rand(rng: &mut Isaac64Rng, colours: usize, bx: f64, by: f64) -> Entity {
+    let x: f64 = rng.gen::<f64>() * bx - bx / 2.0;
+    let y: f64 = rng.gen::<f64>() * by - by / 2.0;
+    new_triangle(rng, colours, x, y)
+}
+
+/// A power-up of a random kind and colour somewhere in the `bx` by `by` box around the middle of the playfield.
+pub fn new_powerup_rand(rng: &mut Isaac64Rng, colours: usize, bx: f64, by: f64) -> Entity {
+    let x: f64 = rng.gen::<f64>() * bx - bx / 2.0;
+    let y: f64 = rng.gen::<f64>() * by - by / 2.0;
+    Entity {
+        kind: Kind::PowerUp { power: POWERS[rng.gen::<u32>() as usize % POWERS.len()] },
+        x: x, y: y,
+        vx: 0.0, vy: 0.0,
+        radius: 30.0,
+        colour: rng.gen::<u32>() as usize % colours,
+        lifetime: Some(15.0)
+    }
+}
+
+/// What the player did during one tick, in playfield coordinates (origin at the centre).
+#[derive(Clone, Copy)]
+pub struct TickInput {
+    pub target_x: f64, pub target_y: f64,
+    pub swap_left: bool,
+    pub swap_right: bool
+}
+
+/// Things that happened during a `Simulation::step`, for the caller to hand to its `events::Subscriber`s.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    AteCircle { colour: usize, on_beat: bool, x: f64, y: f64 },
+    PickedTriangle { colour: usize, x: f64, y: f64 },
+    PickedPowerUp { power: Power, colour: usize, x: f64, y: f64 },
+    LostShield,
+    Died,
+    Beat { beat: u64 },
+    ReachedCombo { level: usize }
+}
+
+/// The gameplay rules, with no window, audio or GPU attached.
+pub struct Simulation {
+    pub player: Player,
+    pub entities: Entities,
+    pub time_elapsed: f64,
+    pub grown: i64,
+    pub score: f64,
+    pub profile: DifficultyProfile,
+    pub col_left: Option<usize>,
+    pub col_right: Option<usize>,
+    // Half the size of the visible playfield, which is centred on the origin.
+    pub half_width: f64, pub half_height: f64,
+    pub alive: bool,
+    // Whether the first step has been taken, which is when beat 0 plays.
+    started: bool,
+    pub seed: usize,
+    pub clock: BeatClock,
+    pub combo: usize,
+    // The powers still going.
+    pub effects: Vec<Effect>,
+    // How big the player was when the combo last went up.
+    combo_size: f64,
+    rng: Isaac64Rng,
+    director: SpawnDirector,
+    grid: Grid,
+    candidates: Vec<usize>,
+    expired: Vec<usize>,
+    next_place_triangle: f64,
+    next_powerup: f64,
+    tris: i64
+}
+
+impl Simulation {
+    /// Two simulations with the same seed and profile fed the same inputs play out identically, on any platform:
+    /// the generator and everything drawn from it are the same width everywhere, unlike `StdRng` and `usize`.
+    pub fn new(profile: DifficultyProfile, seed: usize) -> Simulation {
+        Simulation {
+            player: Player::new(), entities: Entities::new(),
+            time_elapsed: 0.0, grown: 0, score: 0.00001,
+            col_left: None, col_right: None, half_width: 300.0, half_height: 300.0, alive: true, started: false,
+            seed: seed, clock: BeatClock::new(), combo: 0, combo_size: 1.0, effects: Vec::new(), rng: Isaac64Rng::from_seed(&[seed as u64][..]), director: SpawnDirector::new(),
+            grid: Grid::new(CELL_SIZE), candidates: Vec::new(), expired: Vec::new(),
+            next_place_triangle: profile.first_triangle, next_powerup: profile.powerup_interval, tris: 0,
+            profile: profile
+        }
+    }
+    pub fn swap_left(&mut self) {
+        if let Some(col_left) = self.col_left {
+            self.col_left = Some(self.player.colour_state);
+            self.player.colour_state = col_left;
+        }
+    }
+    pub fn swap_right(&mut self) {
+        if let Some(col_right) = self.col_right {
+            self.col_right = Some(self.player.colour_state);
+            self.player.colour_state = col_right;
+        }
+    }
+    /// How long the power has left, if it's going.
+    pub fn effect(&self, power: Power) -> Option<f64> {
+        self.effects.iter().find(|e| e.power == power).map(|e| e.time_left)
+    }
+    fn activate(&mut self, power: Power, colour: usize) {
+        if let Some(duration) = power.duration() {
+            // Picking up one that's already going starts it over.
+            self.effects.retain(|e| e.power != power);
+            self.effects.push(Effect { power: power, time_left: duration });
+        }
+        match power {
+            Power::ColourBomb => {
+                let bombed: Vec<usize> = self.entities.iter()
+                    .filter(|&(_, e)| e.is_circle() && e.colour == colour)
+                    .map(|(id, _)| id).collect();
+                for id in bombed {
+                    self.entities.despawn(id);
+                }
+            }
+            Power::Growth => self.player.radius += powerups::GROWTH_BURST,
+            _ => {}
+        }
+    }
+    // Each combo level needs the player four times as far past the starting size as the last one did,
+    // and shrinking back by a fifth starts over.
+    fn update_combo(&mut self, events: &mut Vec<Event>) {
+        let size = self.player.radius - 39.0;
+        if size >= 4.0 * self.combo_size {
+            self.combo += 1;
+            self.combo_size = size;
+            events.push(Event::ReachedCombo { level: self.combo });
+        }
+        if size < self.combo_size * 0.8 {
+            self.combo = 0;
+            self.combo_size = size.max(1.0);
+        }
+    }
+    pub fn step(&mut self, dt: f64, input: &TickInput) -> Vec<Event> {
+        let mut events = Vec::new();
+        if !self.alive {
+            return events;
+        }
+        if input.swap_left {
+            self.swap_left();
+        }
+        if input.swap_right {
+            self.swap_right();
+        }
+        self.time_elapsed += dt;
+        for effect in &mut self.effects {
+            effect.time_left -= dt;
+        }
+        self.effects.retain(|e| e.time_left > 0.0);
+        // Beat 0 starts with the run, the rest follow at a tempo set by how much the player has grown.
+        let mut beats = self.clock.advance(dt, clock::bpm(self.grown));
+        if !self.started {
+            self.started = true;
+            beats += 1;
+        }
+        let mut bar = false;
+        for i in 0..beats {
+            let beat = self.clock.beat() + 1 + i - beats;
+            events.push(Event::Beat { beat: beat });
+            bar = bar || beat % BEATS_PER_BAR == 0;
+        }
+        self.score += self.profile.score_multiplier * self.time_elapsed.sqrt() * (self.player.radius - 30.0) / 10.0 * ((self.grown + 1) as f64).sqrt() / 10000.0;
+        if self.player.radius > 200.0 {
+            self.player.radius = 200.0;
+        }
+        if self.player.radius > 40.0 {
+            self.player.radius -= dt * self.profile.shrink_rate * (self.player.radius - 40.0) * (self.player.radius - 40.0) / 9000.0;
+        }
+        // Triangles wait for the next bar once they're due.
+        if self.next_place_triangle < 0.0 {
+            if bar {
+                if self.tris == 0 {
+                    self.entities.spawn(new_triangle(&mut self.rng, self.profile.colour_count, 60.0, 60.0));
+                } else {
+                    self.entities.spawn(new_triangle_rand(&mut self.rng, self.profile.colour_count, self.half_width * 2.0, self.half_height * 2.0));
+                }
+                self.tris += 1;
+                self.next_place_triangle = self.profile.triangle_interval;
+            }
+        } else {
+            self.next_place_triangle -= dt;
+        }
+        // Power-ups do too, if the level has them.
+        if self.profile.powerup_interval > 0.0 {
+            if self.next_powerup < 0.0 {
+                if bar {
+                    self.entities.spawn(new_powerup_rand(&mut self.rng, self.profile.colour_count, self.half_width * 2.0, self.half_height * 2.0));
+                    self.next_powerup = self.profile.powerup_interval;
+                }
+            } else {
+                self.next_powerup -= dt;
+            }
+        }
+        // Circles come in waves on the beat, topping the playfield back up, with one extra if the player is owed
+        // something to eat.
+        self.director.update(dt, self.player.colour_state, &self.entities);
+        if beats > 0 {
+            let wanted = self.profile.circle_count(self.time_elapsed, self.grown);
+            let mut wave = 0;
+            let mut circles = self.entities.count(|e| e.is_circle());
+            while (circles < wanted || self.director.overdue(&self.profile)) && wave < MAX_WAVE {
+                let speed = self.profile.circle_speed(self.time_elapsed, self.grown);
+                let colour = self.director.pick(&mut self.rng, &self.profile, self.player.colour_state, [self.col_left, self.col_right]);
+                self.entities.spawn(new_circle(&mut self.rng, colour, self.profile.colour_count, speed, self.half_width, self.half_height));
+                circles += 1;
+                wave += 1;
+            }
+        }
+        let on_beat = self.clock.phase() < RHYTHM_WINDOW || self.clock.phase() > 1.0 - RHYTHM_WINDOW;
+        let p = Vec2::new(self.player.x, self.player.y);
+        let p0 = p;
+        let f = Vec2::new(input.target_x, input.target_y);
+        let delta = f - p;
+        if delta.norm() < 5.0 {
+            self.player.x = f.x;
+            self.player.y = f.y;
+        } else {
+            let delta = delta.normalize();
+            self.player.x += dt * PLAYER_SPEED * delta.x;
+            self.player.y += dt * PLAYER_SPEED * delta.y;
+        }
+        let p1 = Vec2::new(self.player.x, self.player.y);
+        let pv = (p1 - p0) * (1.0 / dt);
+        let (pmin, pmax) = swept_box(p0, p1, self.player.radius);
+        let (dx, dy) = (self.half_width + DESPAWN_MARGIN, self.half_height + DESPAWN_MARGIN);
+        // Everything moves, and whatever has expired or drifted too far out goes before the collision checks.
+        let speed = if self.effect(Power::SlowTime).is_some() { powerups::SLOW_TIME_SPEED } else { 1.0 };
+        let magnet = self.effect(Power::Magnet).is_some();
+        self.grid.clear();
+        self.expired.clear();
+        for (id, e) in self.entities.iter_mut() {
+            // The magnet drags circles along on top of their own movement, which they carry on with once it's gone.
+            if magnet && e.is_circle() && e.colour == self.player.colour_state {
+                let to_player = Vec2::new(self.player.x - e.x, self.player.y - e.y);
+                let distance = to_player.norm();
+                if distance > 1.0 && distance < powerups::MAGNET_RANGE {
+                    let pull = to_player * ((powerups::MAGNET_PULL * dt * speed).min(distance) / distance);
+                    e.x += pull.x;
+                    e.y += pull.y;
+                }
+            }
+            let e0 = Vec2::new(e.x, e.y);
+            if !e.update(dt * speed) || e.x > dx || e.x < -dx || e.y > dy || e.y < -dy {
+                self.expired.push(id);
+            } else {
+                let (min, max) = swept_box(e0, Vec2::new(e.x, e.y), e.radius);
+                self.grid.insert(id, min, max);
+            }
+        }
+        for &id in &self.expired {
+            self.entities.despawn(id);
+        }
+        self.grid.query(pmin, pmax, &mut self.candidates);
+        // By index, since picking up a power-up can change anything in the simulation.
+        for i in 0..self.candidates.len() {
+            let id = self.candidates[i];
+            let e = match self.entities.get(id) {
+                Some(e) => *e,
+                None => continue
+            };
+            if !hits(p0, pv, &self.player, &e, speed, dt) {
+                continue;
+            }
+            match e.kind {
+                Kind::Circle { inside_colour } => {
+                    if e.colour == self.player.colour_state {
+                        self.grown += 1;
+                        self.player.radius += 2.0;
+                        self.player.colour_state = inside_colour;
+                        self.entities.despawn(id);
+                        if on_beat {
+                            self.score += 10.0 * self.grown as f64 * RHYTHM_BONUS;
+                        } else {
+                            self.score += 10.0 * self.grown as f64;
+                        }
+                        events.push(Event::AteCircle { colour: e.colour, on_beat: on_beat, x: e.x, y: e.y });
+                    } else if self.effect(Power::Invulnerable).is_some() {
+                        // Sent back the way it came, straight away from the player and just as fast.
+                        if let Some(e) = self.entities.get_mut(id) {
+                            let away = Vec2::new(e.x - self.player.x, e.y - self.player.y);
+                            let distance = away.norm();
+                            if distance > 0.0 {
+                                let speed = Vec2::new(e.vx, e.vy).norm();
+                                e.vx = away.x * speed / distance;
+                                e.vy = away.y * speed / distance;
+                            }
+                        }
+                    } else if self.col_left == None && self.col_right == None {
+                        self.alive = false;
+                        events.push(Event::Died);
+                        return events;
+                    } else {
+                        if self.col_right == None {
+                            self.col_left = None;
+                        } else {
+                            self.col_right = None;
+                        }
+                        self.entities.despawn(id);
+                        events.push(Event::LostShield);
+                    }
+                }
+                Kind::Triangle => {
+                    if self.col_left == None {
+                        self.col_left = Some(e.colour);
+                    } else if self.col_right == None {
+                        self.col_right = Some(e.colour);
+                    } else {
+                        continue;
+                    }
+                    self.entities.despawn(id);
+                    events.push(Event::PickedTriangle { colour: e.colour, x: e.x, y: e.y });
+                }
+                Kind::PowerUp { power } => {
+                    self.entities.despawn(id);
+                    self.activate(power, e.colour);
+                    events.push(Event::PickedPowerUp { power: power, colour: e.colour, x: e.x, y: e.y });
+                }
+            }
+        }
+        self.update_combo(&mut events);
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entities::{Entity, Kind};
+    use profile;
+
+    // Nothing turns up by itself, so each test only has what it puts on the playfield.
+    fn quiet() -> Simulation {
+        let mut profile = profile::builtin()[2].clone();
+        profile.spawn_time = 0.0;
+        profile.spawn_grown = 0.0;
+        profile.first_triangle = 1000.0;
+        profile.max_edible_gap = 0.0;
+        profile.powerup_interval = 0.0;
+        Simulation::new(profile, 1)
+    }
+
+    // The player staying put in the middle.
+    fn still() -> TickInput {
+        TickInput { target_x: 0.0, target_y: 0.0, swap_left: false, swap_right: false }
+    }
+
+    // A still circle right on top of the player.
+    fn circle(colour: usize, inside_colour: usize) -> Entity {
+        Entity {
+            kind: Kind::Circle { inside_colour: inside_colour },
+            x: 0.0, y: 0.0,
+            vx: 0.0, vy: 0.0,
+            radius: 40.0,
+            colour: colour,
+            lifetime: None
+        }
+    }
+
+    #[test]
+    fn eats_circles_of_its_colour() {
+        let mut sim = quiet();
+        sim.entities.spawn(circle(0, 3));
+        let events = sim.step(STEP, &still());
+        assert!(events.iter().any(|e| match *e { Event::AteCircle { colour: 0, .. } => true, _ => false }));
+        assert_eq!(sim.grown, 1);
+        assert!(sim.score >= 10.0);
+        assert_eq!(sim.player.colour_state, 3);
+        assert_eq!(sim.entities.len(), 0);
+    }
+
+    #[test]
+    fn fast_circles_hit_instead_of_skipping_past() {
+        let mut sim = quiet();
+        // Starts and ends the step well clear of the player, on opposite sides.
+        let mut e = circle(0, 3);
+        e.x = -100.0;
+        e.vx = 200.0 / STEP;
+        sim.entities.spawn(e);
+        let events = sim.step(STEP, &still());
+        assert!(events.iter().any(|e| match *e { Event::AteCircle { .. } => true, _ => false }));
+        assert_eq!(sim.grown, 1);
+    }
+
+    #[test]
+    fn loses_a_shield_to_other_colours() {
+        let mut sim = quiet();
+        sim.col_left = Some(2);
+        sim.entities.spawn(circle(1, 3));
+        let events = sim.step(STEP, &still());
+        assert!(events.contains(&Event::LostShield));
+        assert!(sim.alive);
+        assert_eq!(sim.col_left, None);
+        assert_eq!(sim.grown, 0);
+        assert_eq!(sim.entities.len(), 0);
+    }
+
+    #[test]
+    fn bounces_circles_while_invulnerable() {
+        let mut sim = quiet();
+        sim.effects.push(Effect { power: Power::Invulnerable, time_left: 1.0 });
+        let mut e = circle(1, 3);
+        e.x = 10.0;
+        e.vx = -100.0;
+        let id = sim.entities.spawn(e);
+        let events = sim.step(STEP, &still());
+        assert!(events.iter().all(|e| *e != Event::Died && *e != Event::LostShield));
+        assert!(sim.alive);
+        assert!(sim.entities.get(id).unwrap().vx > 0.0);
+    }
+
+    #[test]
+    fn magnet_leaves_velocity_alone() {
+        let mut sim = quiet();
+        sim.effects.push(Effect { power: Power::Magnet, time_left: 0.5 });
+        let mut e = circle(0, 3);
+        e.x = 200.0;
+        e.vy = 50.0;
+        let id = sim.entities.spawn(e);
+        for _ in 0..60 {
+            sim.step(STEP, &still());
+        }
+        let e = *sim.entities.get(id).unwrap();
+        assert!(e.x < 200.0);
+        assert_eq!((e.vx, e.vy), (0.0, 50.0));
+    }
+
+    #[test]
+    fn dies_without_shields() {
+        let mut sim = quiet();
+        sim.entities.spawn(circle(1, 3));
+        sim.entities.spawn(circle(2, 3));
+        let events = sim.step(STEP, &still());
+        assert_eq!(events.last(), Some(&Event::Died));
+        assert!(!sim.alive);
+        // Nothing after the fatal hit is looked at.
+        assert_eq!(sim.entities.len(), 2);
+        assert!(sim.step(STEP, &still()).is_empty());
+    }
+
+    #[test]
+    fn same_seed_and_inputs_play_out_the_same() {
+        let run = || {
+            let mut sim = Simulation::new(profile::builtin()[2].clone(), 42);
+            let mut events = Vec::new();
+            for i in 0..6000 {
+                let t = i as f64;
+                let input = TickInput {
+                    target_x: (t * 0.01).sin() * 250.0, target_y: (t * 0.007).cos() * 250.0,
+                    swap_left: i % 300 == 0, swap_right: i % 450 == 0
+                };
+                events.extend(sim.step(STEP, &input));
+            }
+            (events, sim.score, sim.grown, sim.alive)
+        };
+        let (events, score, grown, alive) = run();
+        assert!(!events.is_empty());
+        assert_eq!(run(), (events, score, grown, alive));
+    }
+}
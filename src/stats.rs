@@ -0,0 +1,48 @@
+use std::io;
+use std::path::Path;
+
+use audio::Audio;
+use events::Subscriber;
+use paths;
+use simulation::{Event, Simulation};
+
+/// Totals over every run played, replays aside.
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct Stats {
+    pub circles_eaten: u64,
+    pub on_beat: u64,
+    pub triangles_picked: u64,
+    pub shields_lost: u64,
+    pub deaths: u64,
+    pub best_combo: u64
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats { circles_eaten: 0, on_beat: 0, triangles_picked: 0, shields_lost: 0, deaths: 0, best_combo: 0 }
+    }
+    pub fn load(path: &Path) -> Stats {
+        paths::load_toml(path, "stats").unwrap_or(Stats::new())
+    }
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        paths::save_toml(path, self)
+    }
+}
+
+impl Subscriber for Stats {
+    fn on_event(&mut self, event: &Event, _sim: &Simulation, _audio: &Audio) {
+        match *event {
+            Event::AteCircle { on_beat, .. } => {
+                self.circles_eaten += 1;
+                if on_beat {
+                    self.on_beat += 1;
+                }
+            }
+            Event::PickedTriangle { .. } => self.triangles_picked += 1,
+            Event::LostShield => self.shields_lost += 1,
+            Event::Died => self.deaths += 1,
+            Event::ReachedCombo { level } => self.best_combo = self.best_combo.max(level as u64),
+            Event::PickedPowerUp { .. } | Event::Beat { .. } => {}
+        }
+    }
+}